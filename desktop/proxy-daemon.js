@@ -4,8 +4,21 @@
  * Desktop daemon for running the Antigravity proxy inside a Tauri-managed Node process.
  */
 
-import { startProxy, stopProxy, getStatus } from '../src/index.js';
-import { ensureClaudeConfig } from '../src/services/claude-config.js';
+import { readFileSync } from 'fs';
+import { createInterface } from 'readline';
+import {
+    startProxy,
+    stopProxy,
+    getStatus,
+    switchAccount,
+    reloadConfig,
+    flushFlows
+} from '../src/index.js';
+
+// Version of the stdout event protocol spoken with the desktop shell.
+// Bump together with PROTOCOL_VERSION in tauri/src-tauri/src/protocol.rs.
+const PROTOCOL_VERSION = 1;
+const CAPABILITIES = ['status', 'heartbeat', 'stop-ack', 'control'];
 
 const port = process.env.ANTIGRAVITY_PORT ? Number(process.env.ANTIGRAVITY_PORT) : undefined;
 const host = process.env.ANTIGRAVITY_HOST || undefined;
@@ -18,12 +31,65 @@ function emit(event, payload = {}) {
     }
 }
 
-async function main() {
+// Commands the shell can send as JSON lines on stdin: { id, command, args }.
+// Each one is answered with a `response` event carrying the same id.
+const COMMANDS = {
+    status: () => getStatus(),
+    'switch-account': (args) => switchAccount(args.email),
+    'reload-config': () => reloadConfig(),
+    'flush-flows': () => flushFlows()
+};
+
+async function handleCommand(line) {
+    let request;
     try {
-        await ensureClaudeConfig({ port });
+        request = JSON.parse(line);
+    } catch {
+        emit('response', { id: null, ok: false, error: 'Invalid JSON command' });
+        return;
+    }
+
+    const { id = null, command, args = {} } = request || {};
+    const handler = COMMANDS[command];
+    if (!handler) {
+        emit('response', { id, ok: false, error: `Unknown command: ${command}` });
+        return;
+    }
+
+    try {
+        const result = await handler(args);
+        emit('response', { id, ok: true, result: result ?? null });
     } catch (error) {
-        emit('error', { message: `Failed to configure Claude CLI: ${error.message}` });
+        emit('response', { id, ok: false, error: error.message });
     }
+}
+
+function listenForCommands() {
+    const rl = createInterface({ input: process.stdin });
+    rl.on('line', (line) => {
+        if (line.trim()) {
+            handleCommand(line);
+        }
+    });
+}
+
+function readBackendVersion() {
+    try {
+        const pkg = JSON.parse(readFileSync(new URL('../package.json', import.meta.url), 'utf-8'));
+        return pkg.version || null;
+    } catch {
+        return null;
+    }
+}
+
+async function main() {
+    emit('hello', {
+        protocol: PROTOCOL_VERSION,
+        capabilities: CAPABILITIES,
+        version: readBackendVersion(),
+        node: process.version,
+        pid: process.pid
+    });
 
     try {
         await startProxy({ port, host });
@@ -33,6 +99,8 @@ async function main() {
         process.exit(1);
     }
 
+    listenForCommands();
+
     const interval = setInterval(() => {
         emit('status', { phase: 'heartbeat', snapshot: getStatus() });
     }, 5000);
@@ -469,6 +469,31 @@ export class AccountManager {
         return { account: nextAccount, waitMs: 0 };
     }
 
+    /**
+     * Make the given account the active one and pin the time window lock to it.
+     * Used by the desktop shell for manual account switching.
+     * @param {string} email - Email of the account to activate
+     * @returns {Object} The activated account
+     */
+    switchTo(email) {
+        const index = this.#accounts.findIndex(a => a.email === email);
+        if (index === -1) {
+            throw new Error(`Unknown account: ${email}`);
+        }
+        const account = this.#accounts[index];
+        if (account.isInvalid) {
+            throw new Error(`Account ${email} is marked invalid`);
+        }
+
+        this.#currentIndex = index;
+        this.#lastUsedAccount = email;
+        this.#lastUsedTime = nowMs();
+        console.log(`[AccountManager] Manually switched to: ${email}`);
+
+        this.saveToDisk();
+        return account;
+    }
+
     /**
      * Mark an account as rate-limited
      * @param {string} email - Email of the account to mark
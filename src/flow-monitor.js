@@ -181,6 +181,17 @@ export class FlowMonitor {
         this.flowMap.clear();
     }
 
+    /**
+     * Wait for pending persistence, then drop the in-memory buffer.
+     * @returns {Promise<number>} Number of flows removed from memory
+     */
+    async flush() {
+        await this.persistChain.catch(() => {});
+        const count = this.flows.length;
+        this.reset();
+        return count;
+    }
+
     _trim() {
         if (this.flows.length <= this.maxEntries) return;
         const removed = this.flows.splice(this.maxEntries);
@@ -4,7 +4,12 @@
  */
 
 import { fileURLToPath } from 'url';
-import app, { getRuntimeSnapshot } from './server.js';
+import app, {
+    getRuntimeSnapshot,
+    switchActiveAccount,
+    reloadRuntimeConfig,
+    flushFlows as flushFlowBuffer
+} from './server.js';
 import { DEFAULT_PORT } from './constants.js';
 import { getConfig } from './services/config-service.js';
 import { createBackup } from './services/backup-service.js';
@@ -146,6 +151,19 @@ export async function stopProxy() {
     });
 }
 
+export async function switchAccount(email) {
+    if (!email) throw new Error('Account email is required');
+    return switchActiveAccount(email);
+}
+
+export function reloadConfig() {
+    return reloadRuntimeConfig();
+}
+
+export async function flushFlows() {
+    return flushFlowBuffer();
+}
+
 export function getStatus() {
     let accountSnapshot = null;
     try {
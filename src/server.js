@@ -21,7 +21,7 @@ import {
 import { AccountManager } from './account-manager.js';
 import { formatDuration } from './utils/helpers.js';
 import { flowMonitor, readPersistedFlows, getDailyLogPath, formatFlowDayKey } from './flow-monitor.js';
-import { getConfig, updateConfig } from './services/config-service.js';
+import { getConfig, updateConfig, reloadConfig } from './services/config-service.js';
 import { createBackup, listBackups } from './services/backup-service.js';
 import { openAIChatToAnthropic, anthropicToOpenAIChat } from './utils/openai-adapter.js';
 import { createDefaultRegistry } from './providers/provider-registry.js';
@@ -991,6 +991,32 @@ export function getRuntimeSnapshot() {
     }
 }
 
+/**
+ * Manually switch the active account (desktop control channel)
+ */
+export async function switchActiveAccount(email) {
+    await ensureInitialized();
+    const account = accountManager.switchTo(email);
+    return { currentAccount: account.email };
+}
+
+/**
+ * Re-read config.json from disk and apply runtime-tunable values
+ */
+export function reloadRuntimeConfig() {
+    const config = reloadConfig();
+    flowMonitor.setMaxEntries(config.maxFlowEntries || 200);
+    return sanitizeConfig(config);
+}
+
+/**
+ * Persist pending flow records and clear the in-memory flow buffer
+ */
+export async function flushFlows() {
+    const flushed = await flowMonitor.flush();
+    return { flushed };
+}
+
 // Export for proactive initialization
 export { ensureInitialized };
 
@@ -1,9 +1,24 @@
 import { homedir } from 'os';
 import { join } from 'path';
-import { existsSync, mkdirSync, readFileSync, writeFileSync } from 'fs';
+import {
+    closeSync,
+    existsSync,
+    fsyncSync,
+    mkdirSync,
+    openSync,
+    readdirSync,
+    readFileSync,
+    renameSync,
+    statSync,
+    unlinkSync,
+    writeFileSync
+} from 'fs';
 
 const CLAUDE_DIR = join(homedir(), '.claude');
 const CLAUDE_SETTINGS_PATH = join(CLAUDE_DIR, 'settings.json');
+// Shared with the desktop app, which lists and restores these backups.
+const BACKUP_DIR = join(homedir(), '.antigravity-proxy', 'claude-backups');
+const MAX_BACKUPS = 10;
 
 const DEFAULT_ENV = {
     ANTHROPIC_AUTH_TOKEN: 'test',
@@ -20,6 +35,113 @@ function ensureClaudeDir() {
     }
 }
 
+function readRaw() {
+    try {
+        return readFileSync(CLAUDE_SETTINGS_PATH, 'utf-8');
+    } catch (err) {
+        if (err.code === 'ENOENT') {
+            return null;
+        }
+        throw err;
+    }
+}
+
+function parseSettings(raw) {
+    if (raw === null) {
+        return {};
+    }
+    try {
+        const parsed = JSON.parse(raw);
+        if (parsed && typeof parsed === 'object') {
+            return parsed;
+        }
+    } catch {
+        // Ignore parse errors, fallback to empty
+    }
+    return {};
+}
+
+function backupSeq(name) {
+    const seq = Number(name.slice(0, -'.json'.length).split('.').pop());
+    return Number.isInteger(seq) ? seq : 0;
+}
+
+// Newest first by modification time, then sequence, like the desktop app.
+function listBackups() {
+    let names;
+    try {
+        names = readdirSync(BACKUP_DIR);
+    } catch {
+        return [];
+    }
+    return names
+        .filter((name) => name.startsWith('settings.') && name.endsWith('.json'))
+        .map((name) => {
+            const path = join(BACKUP_DIR, name);
+            return { path, seq: backupSeq(name), mtime: statSync(path).mtimeMs };
+        })
+        .sort((a, b) => b.mtime - a.mtime || b.seq - a.seq);
+}
+
+// settings.20240102-030405.123.000042.json: the UTC time to the millisecond
+// plus one past the newest backup's sequence number.
+function backUp(contents) {
+    mkdirSync(BACKUP_DIR, { recursive: true });
+    const stamp = new Date().toISOString()
+        .replace(/[-:]/g, '')
+        .replace('T', '-')
+        .replace('Z', '');
+    const seq = listBackups().reduce((max, backup) => Math.max(max, backup.seq), 0) + 1;
+    const target = join(BACKUP_DIR, `settings.${stamp}.${String(seq).padStart(6, '0')}.json`);
+    writeFileSync(target, contents);
+    for (const stale of listBackups().slice(MAX_BACKUPS)) {
+        try {
+            unlinkSync(stale.path);
+        } catch {
+            // Pruning is best effort
+        }
+    }
+}
+
+// Backs up `previous`, writes `settings` to a temp file, fsyncs it and
+// renames it over settings.json, so Claude never sees a half-written file.
+// Refuses if the file changed since `previous` was read.
+function replaceSettings(previous, settings) {
+    ensureClaudeDir();
+    if (previous !== null) {
+        backUp(previous);
+    }
+    const tmp = join(CLAUDE_DIR, `.settings.json.${process.pid}.tmp`);
+    try {
+        const fd = openSync(tmp, 'w');
+        try {
+            writeFileSync(fd, JSON.stringify(settings, null, 2));
+            fsyncSync(fd);
+        } finally {
+            closeSync(fd);
+        }
+        if (readRaw() !== previous) {
+            throw new Error(`${CLAUDE_SETTINGS_PATH} changed while it was being updated; try again`);
+        }
+        renameSync(tmp, CLAUDE_SETTINGS_PATH);
+    } catch (err) {
+        try {
+            unlinkSync(tmp);
+        } catch {
+            // Already renamed or never created
+        }
+        throw err;
+    }
+    if (process.platform !== 'win32') {
+        const dir = openSync(CLAUDE_DIR, 'r');
+        try {
+            fsyncSync(dir);
+        } finally {
+            closeSync(dir);
+        }
+    }
+}
+
 export function getClaudeSettings() {
     ensureClaudeDir();
     if (!existsSync(CLAUDE_SETTINGS_PATH)) {
@@ -46,7 +168,9 @@ export function buildProxyEnv(port = 8080) {
 
 export function ensureClaudeConfig(options = {}) {
     const port = options.port || process.env.PORT || 8080;
-    const settings = getClaudeSettings();
+    ensureClaudeDir();
+    const raw = readRaw();
+    const settings = parseSettings(raw);
     const currentEnv = settings.env || {};
     const mergedEnv = { ...currentEnv, ...buildProxyEnv(port) };
 
@@ -55,8 +179,7 @@ export function ensureClaudeConfig(options = {}) {
         env: mergedEnv
     };
 
-    ensureClaudeDir();
-    writeFileSync(CLAUDE_SETTINGS_PATH, JSON.stringify(next, null, 2));
+    replaceSettings(raw, next);
     return next;
 }
 
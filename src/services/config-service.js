@@ -69,6 +69,11 @@ export function getConfig() {
     return { ...cachedConfig };
 }
 
+export function reloadConfig() {
+    cachedConfig = null;
+    return getConfig();
+}
+
 export function updateConfig(patch) {
     const current = getConfig();
     const allowLanAccess = typeof patch.allowLanAccess === 'boolean' ? patch.allowLanAccess : current.allowLanAccess;
@@ -0,0 +1,784 @@
+use std::{
+    cmp::Reverse,
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, Utc};
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Mirrors `DEFAULT_ENV` in `src/services/claude-config.js`; keep the two in
+/// step.
+const DEFAULT_ENV: &[(&str, &str)] = &[
+    ("ANTHROPIC_AUTH_TOKEN", "test"),
+    ("ANTHROPIC_MODEL", "claude-opus-4-5-thinking"),
+    ("ANTHROPIC_DEFAULT_OPUS_MODEL", "claude-opus-4-5-thinking"),
+    ("ANTHROPIC_DEFAULT_SONNET_MODEL", "claude-sonnet-4-5-thinking"),
+    ("ANTHROPIC_DEFAULT_HAIKU_MODEL", "claude-sonnet-4-5"),
+    ("CLAUDE_CODE_SUBAGENT_MODEL", "claude-opus-4-5-thinking"),
+];
+
+/// How many backups of `settings.json` to keep.
+const MAX_BACKUPS: usize = 10;
+
+/// Kept next to the backups while the proxy env is attached.
+const ATTACHMENT_FILE: &str = "attached.json";
+
+/// Whether Claude's `settings.json` points at the proxy, as shown in the UI.
+/// Secret values are masked throughout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeConfigStatus {
+    pub healthy: bool,
+    pub port: u16,
+    pub settings_path: String,
+    pub expected: Value,
+    /// The file's `env` object, or `{}`.
+    pub current: Value,
+    /// One entry per key of `expected`, in order.
+    pub diff: Vec<EnvDiff>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Added,
+    Changed,
+    Unchanged,
+}
+
+/// What a repair would do to one `env` key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnvDiff {
+    pub key: String,
+    pub kind: DiffKind,
+    /// `None` when the key is missing.
+    pub current: Option<String>,
+    pub expected: String,
+    /// Whether both values are masked.
+    pub secret: bool,
+}
+
+/// `~/.claude/settings.json`.
+pub fn settings_path() -> PathBuf {
+    home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".claude")
+        .join("settings.json")
+}
+
+/// Where copies of `settings.json` go before the shell changes it.
+pub fn backup_dir() -> PathBuf {
+    crate::settings::state_dir().join("claude-backups")
+}
+
+/// `buildProxyEnv(port)`: the model defaults plus `ANTHROPIC_BASE_URL`.
+pub fn expected_env(port: u16) -> Map<String, Value> {
+    let mut env: Map<String, Value> = DEFAULT_ENV
+        .iter()
+        .map(|(key, value)| (key.to_string(), Value::from(*value)))
+        .collect();
+    env.insert(
+        "ANTHROPIC_BASE_URL".to_string(),
+        Value::from(format!("http://localhost:{port}")),
+    );
+    env
+}
+
+/// The whole settings object. A missing file reads as `{}`; a file that is
+/// not a JSON object is an error, so a repair never overwrites it.
+pub fn read_settings(path: &Path) -> Result<Map<String, Value>, String> {
+    parse_settings(path, read_raw(path)?.as_deref())
+}
+
+/// The file's bytes, `None` if it does not exist.
+fn read_raw(path: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(path) {
+        Ok(raw) => Ok(Some(raw)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("Unable to read {}: {err}", path.display())),
+    }
+}
+
+fn parse_settings(path: &Path, raw: Option<&str>) -> Result<Map<String, Value>, String> {
+    let Some(raw) = raw.filter(|raw| !raw.trim().is_empty()) else {
+        return Ok(Map::new());
+    };
+    match serde_json::from_str::<Value>(raw) {
+        Ok(Value::Object(settings)) => Ok(settings),
+        Ok(_) => Err(format!("{} is not a JSON object", path.display())),
+        Err(err) => Err(format!("{} is not valid JSON: {err}", path.display())),
+    }
+}
+
+/// Key-level comparison of `env` against `expected`, in `expected` order.
+pub fn diff(env: &Map<String, Value>, expected: &Map<String, Value>) -> Vec<EnvDiff> {
+    expected
+        .iter()
+        .map(|(key, value)| {
+            let current = env.get(key);
+            let kind = match current {
+                None => DiffKind::Added,
+                Some(current) if current != value => DiffKind::Changed,
+                Some(_) => DiffKind::Unchanged,
+            };
+            let secret = is_secret(key);
+            let show = |value: &Value| {
+                let text = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+                if secret {
+                    mask(&text)
+                } else {
+                    text
+                }
+            };
+            EnvDiff {
+                key: key.clone(),
+                kind,
+                current: current.map(show),
+                expected: show(value),
+                secret,
+            }
+        })
+        .collect()
+}
+
+/// `{ ...settings, env: { ...settings.env, ...expected } }`: every other key,
+/// in `env` and outside it, is kept where it was. With `keys`, only those
+/// keys of `expected` are applied.
+pub fn merge(
+    mut settings: Map<String, Value>,
+    expected: &Map<String, Value>,
+    keys: Option<&[String]>,
+) -> Map<String, Value> {
+    let env = settings
+        .entry("env")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !env.is_object() {
+        *env = Value::Object(Map::new());
+    }
+    if let Value::Object(env) = env {
+        for (key, value) in expected {
+            if keys.map_or(true, |keys| keys.contains(key)) {
+                env.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    settings
+}
+
+/// Keys whose values are credentials rather than configuration.
+fn is_secret(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    ["TOKEN", "KEY", "SECRET", "PASSWORD"]
+        .iter()
+        .any(|word| key.contains(word))
+}
+
+/// `••••` plus the last two characters of long values, so a changed secret
+/// can still be told apart.
+fn mask(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() > 8 {
+        format!("••••{}", chars[chars.len() - 2..].iter().collect::<String>())
+    } else {
+        "••••".to_string()
+    }
+}
+
+fn masked(env: &Map<String, Value>) -> Map<String, Value> {
+    env.iter()
+        .map(|(key, value)| {
+            let value = match value.as_str() {
+                Some(text) if is_secret(key) => Value::from(mask(text)),
+                _ => value.clone(),
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+fn env_of(settings: &Map<String, Value>) -> Map<String, Value> {
+    match settings.get("env") {
+        Some(Value::Object(env)) => env.clone(),
+        _ => Map::new(),
+    }
+}
+
+/// `needsClaudeReconfigure` plus everything the UI shows. An unreadable file
+/// counts as unhealthy with an empty `current`.
+pub fn check(path: &Path, port: u16) -> ClaudeConfigStatus {
+    let settings = read_settings(path).unwrap_or_default();
+    status(path, port, &settings)
+}
+
+/// Merges the proxy env (or only `keys` of it) into the file and returns the
+/// new status. The previous file is backed up to `backups` first.
+pub fn repair(
+    path: &Path,
+    backups: &Path,
+    port: u16,
+    keys: Option<&[String]>,
+) -> Result<ClaudeConfigStatus, String> {
+    let expected = expected_env(port);
+    if let Some(keys) = keys {
+        if let Some(unknown) = keys.iter().find(|key| !expected.contains_key(*key)) {
+            return Err(format!("{unknown} is not managed by the proxy"));
+        }
+        if keys.is_empty() {
+            return Err("No keys selected".to_string());
+        }
+    }
+    let raw = read_raw(path)?;
+    let before = parse_settings(path, raw.as_deref())?;
+    let mut attachment = read_attachment(backups);
+    let env_before = env_of(&before);
+    for (key, value) in &expected {
+        if keys.map_or(true, |keys| keys.contains(key)) {
+            // Only the first attach knows what the keys held before the proxy.
+            if !attachment.previous.contains_key(key) {
+                let previous = env_before.get(key).cloned().unwrap_or(Value::Null);
+                attachment.previous.insert(key.clone(), previous);
+            }
+            attachment.injected.insert(key.clone(), value.clone());
+        }
+    }
+    attachment.reattach = None;
+
+    let settings = merge(before, &expected, keys);
+    replace(path, backups, raw.as_deref(), &pretty(&settings)?)?;
+    write_attachment(backups, &attachment)?;
+    Ok(status(path, port, &settings))
+}
+
+/// Undoes `repair`: every key it injected that still holds the injected
+/// value goes back to what it was before, or is removed if it was missing.
+/// Keys changed since are left alone. Without a record of the attach there
+/// is nothing to restore the keys to, so nothing is touched.
+pub fn detach(path: &Path, backups: &Path, port: u16) -> Result<ClaudeConfigStatus, String> {
+    let attachment = read_attachment(backups);
+    if attachment.injected.is_empty() {
+        return Err(format!(
+            "The app has no record of attaching {}; restore a backup instead",
+            path.display()
+        ));
+    }
+    let injected = &attachment.injected;
+    let raw = read_raw(path)?;
+    let mut settings = parse_settings(path, raw.as_deref())?;
+    let mut changed = false;
+    if let Some(Value::Object(env)) = settings.get_mut("env") {
+        for (key, value) in injected {
+            if env.get(key) != Some(value) {
+                continue;
+            }
+            match attachment.previous.get(key) {
+                Some(previous) if !previous.is_null() => {
+                    env.insert(key.clone(), previous.clone());
+                }
+                _ => {
+                    env.shift_remove(key);
+                }
+            }
+            changed = true;
+        }
+    }
+    if changed {
+        replace(path, backups, raw.as_deref(), &pretty(&settings)?)?;
+    }
+    let _ = fs::remove_file(backups.join(ATTACHMENT_FILE));
+    Ok(status(path, port, &settings))
+}
+
+/// Keys injected by a `repair` that has not been detached; empty if none.
+pub fn attached_keys(backups: &Path) -> Vec<String> {
+    read_attachment(backups).injected.keys().cloned().collect()
+}
+
+/// Asks the next launch to re-attach `keys`; used when detaching on quit.
+pub fn request_reattach(backups: &Path, keys: Vec<String>) -> Result<(), String> {
+    let attachment = Attachment {
+        reattach: Some(keys),
+        ..Attachment::default()
+    };
+    write_attachment(backups, &attachment)
+}
+
+/// The keys passed to `request_reattach`, unless an attach happened since.
+pub fn reattach_requested(backups: &Path) -> Option<Vec<String>> {
+    read_attachment(backups).reattach
+}
+
+/// What the shell's attach changed, so `detach` can undo exactly that.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Attachment {
+    /// The values `repair` wrote.
+    injected: Map<String, Value>,
+    /// What those keys held before the first attach; `null` where missing.
+    previous: Map<String, Value>,
+    reattach: Option<Vec<String>>,
+}
+
+fn read_attachment(backups: &Path) -> Attachment {
+    fs::read_to_string(backups.join(ATTACHMENT_FILE))
+        .ok()
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+fn write_attachment(backups: &Path, attachment: &Attachment) -> Result<(), String> {
+    let path = backups.join(ATTACHMENT_FILE);
+    fs::create_dir_all(backups)
+        .map_err(|err| format!("Unable to create {}: {err}", backups.display()))?;
+    let json = serde_json::to_string_pretty(attachment).map_err(|err| err.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)
+        .and_then(|()| fs::rename(&tmp, &path))
+        .map_err(|err| format!("Unable to write {}: {err}", path.display()))
+}
+
+/// Puts the backup called `name` back in place byte for byte, backing up
+/// what it replaces.
+pub fn restore(path: &Path, backups: &Path, name: &str, port: u16) -> Result<ClaudeConfigStatus, String> {
+    let backup = list_backups(backups)
+        .into_iter()
+        .find(|backup| backup.name == name)
+        .ok_or_else(|| format!("No backup named {name}"))?;
+    let restored = fs::read_to_string(&backup.path)
+        .map_err(|err| format!("Unable to read {}: {err}", backup.path.display()))?;
+    let settings = parse_settings(&backup.path, Some(&restored))?;
+    let raw = read_raw(path)?;
+    replace(path, backups, raw.as_deref(), &restored)?;
+    Ok(status(path, port, &settings))
+}
+
+fn pretty(settings: &Map<String, Value>) -> Result<String, String> {
+    serde_json::to_string_pretty(settings).map_err(|err| err.to_string())
+}
+
+/// Backs up `previous`, then swaps `text` in through a synced temporary
+/// file. Fails without writing if the file changed since `previous` was read
+/// (Claude Code writes it too).
+fn replace(
+    path: &Path,
+    backups: &Path,
+    previous: Option<&str>,
+    text: &str,
+) -> Result<(), String> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir).map_err(|err| format!("Unable to create {}: {err}", dir.display()))?;
+    if let Some(previous) = previous {
+        back_up(backups, previous)?;
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "settings.json".to_string());
+    let tmp = dir.join(format!(".{file_name}.{}.tmp", std::process::id()));
+    let written = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()
+    })();
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Unable to write {}: {err}", tmp.display()));
+    }
+
+    if read_raw(path)?.as_deref() != previous {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("{} changed while it was being updated; try again", path.display()));
+    }
+    if let Err(err) = fs::rename(&tmp, path) {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Unable to replace {}: {err}", path.display()));
+    }
+    // Make the rename itself durable.
+    #[cfg(not(windows))]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// A copy of `settings.json` taken before the shell changed it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigBackup {
+    pub name: String,
+    pub path: PathBuf,
+    pub created: Option<String>,
+    pub size: u64,
+}
+
+/// `settings.20240102-030405.123.000042.json`: the time to the millisecond
+/// plus a sequence number one past the newest backup's, so a name is never
+/// reused after pruning. Old backups beyond `MAX_BACKUPS` are removed.
+fn back_up(backups: &Path, contents: &str) -> Result<(), String> {
+    fs::create_dir_all(backups)
+        .map_err(|err| format!("Unable to create {}: {err}", backups.display()))?;
+    let stamp = Utc::now().format("%Y%m%d-%H%M%S%.3f");
+    let seq = list_backups(backups)
+        .iter()
+        .map(|backup| backup_seq(&backup.name))
+        .max()
+        .map_or(1, |seq| seq + 1);
+    let target = backups.join(format!("settings.{stamp}.{seq:06}.json"));
+    fs::write(&target, contents)
+        .map_err(|err| format!("Unable to back up to {}: {err}", target.display()))?;
+    for stale in list_backups(backups).into_iter().skip(MAX_BACKUPS) {
+        let _ = fs::remove_file(stale.path);
+    }
+    Ok(())
+}
+
+/// Backups in `backups`, newest first by modification time, then sequence.
+pub fn list_backups(backups: &Path) -> Vec<ConfigBackup> {
+    let mut found: Vec<(Option<SystemTime>, u64, ConfigBackup)> = fs::read_dir(backups)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !(name.starts_with("settings.") && name.ends_with(".json")) {
+                return None;
+            }
+            let metadata = entry.metadata().ok()?;
+            let modified = metadata.modified().ok();
+            let backup = ConfigBackup {
+                created: modified.map(|time| DateTime::<Utc>::from(time).to_rfc3339()),
+                size: metadata.len(),
+                path: entry.path(),
+                name,
+            };
+            Some((modified, backup_seq(&backup.name), backup))
+        })
+        .collect();
+    found.sort_by_key(|(modified, seq, _)| Reverse((*modified, *seq)));
+    found.into_iter().map(|(_, _, backup)| backup).collect()
+}
+
+/// `settings.20240102-030405.123.000042.json` -> 42. Names from before the
+/// sequence was added count as 0.
+fn backup_seq(name: &str) -> u64 {
+    name.trim_start_matches("settings.")
+        .trim_end_matches(".json")
+        .rsplit_once('.')
+        .and_then(|(_, seq)| seq.parse().ok())
+        .unwrap_or(0)
+}
+
+fn status(path: &Path, port: u16, settings: &Map<String, Value>) -> ClaudeConfigStatus {
+    let expected = expected_env(port);
+    let current = env_of(settings);
+    let diff = diff(&current, &expected);
+    ClaudeConfigStatus {
+        healthy: diff.iter().all(|entry| entry.kind == DiffKind::Unchanged),
+        port,
+        settings_path: path.display().to_string(),
+        expected: Value::Object(masked(&expected)),
+        current: Value::Object(masked(&current)),
+        diff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use serde_json::json;
+
+    const PORT: u16 = 8080;
+
+    /// A scratch directory holding `settings.json` and the backups.
+    fn scratch(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("claude-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        (dir.join("settings.json"), dir.join("backups"))
+    }
+
+    fn object(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            other => panic!("not an object: {other}"),
+        }
+    }
+
+    fn proxy_env() -> Value {
+        json!({
+            "ANTHROPIC_AUTH_TOKEN": "test",
+            "ANTHROPIC_MODEL": "claude-opus-4-5-thinking",
+            "ANTHROPIC_DEFAULT_OPUS_MODEL": "claude-opus-4-5-thinking",
+            "ANTHROPIC_DEFAULT_SONNET_MODEL": "claude-sonnet-4-5-thinking",
+            "ANTHROPIC_DEFAULT_HAIKU_MODEL": "claude-sonnet-4-5",
+            "CLAUDE_CODE_SUBAGENT_MODEL": "claude-opus-4-5-thinking",
+            "ANTHROPIC_BASE_URL": "http://localhost:8080",
+        })
+    }
+
+    fn added(key: &str, expected: &str, secret: bool) -> EnvDiff {
+        EnvDiff {
+            key: key.to_string(),
+            kind: DiffKind::Added,
+            current: None,
+            expected: expected.to_string(),
+            secret,
+        }
+    }
+
+    fn all_added() -> Vec<EnvDiff> {
+        vec![
+            added("ANTHROPIC_AUTH_TOKEN", "••••", true),
+            added("ANTHROPIC_MODEL", "claude-opus-4-5-thinking", false),
+            added("ANTHROPIC_DEFAULT_OPUS_MODEL", "claude-opus-4-5-thinking", false),
+            added("ANTHROPIC_DEFAULT_SONNET_MODEL", "claude-sonnet-4-5-thinking", false),
+            added("ANTHROPIC_DEFAULT_HAIKU_MODEL", "claude-sonnet-4-5", false),
+            added("CLAUDE_CODE_SUBAGENT_MODEL", "claude-opus-4-5-thinking", false),
+            added("ANTHROPIC_BASE_URL", "http://localhost:8080", false),
+        ]
+    }
+
+    /// `tests/fixtures/claude/<name>.input.json` and the settings a merge of
+    /// the proxy env must produce from it, byte for byte.
+    macro_rules! golden {
+        ($name:literal) => {
+            (
+                $name,
+                include_str!(concat!("../tests/fixtures/claude/", $name, ".input.json")),
+                include_str!(concat!("../tests/fixtures/claude/", $name, ".expected.json")),
+            )
+        };
+    }
+
+    #[test]
+    fn merge_matches_the_golden_files() {
+        let selected = ["ANTHROPIC_BASE_URL".to_string()];
+        let cases = [
+            (golden!("unrelated-keys"), None),
+            (golden!("non-object-env"), None),
+            (golden!("key-order"), None),
+            (golden!("selected-keys"), Some(&selected[..])),
+        ];
+        for ((name, input, expected), keys) in cases {
+            let input = object(serde_json::from_str(input).unwrap());
+            let merged = merge(input, &expected_env(PORT), keys);
+            assert_eq!(pretty(&merged).unwrap() + "\n", expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn repair_writes_the_golden_file() {
+        let (name, input, expected) = golden!("unrelated-keys");
+        let (path, backups) = scratch(name);
+        fs::write(&path, input).unwrap();
+        repair(&path, &backups, PORT, None).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap() + "\n", expected);
+        assert_eq!(fs::read_to_string(&list_backups(&backups)[0].path).unwrap(), input);
+    }
+
+    #[test]
+    fn diff_reports_changed_and_missing_keys() {
+        let (_, input, _) = golden!("unrelated-keys");
+        let input = object(serde_json::from_str(input).unwrap());
+        let mut golden = all_added();
+        golden[1] = EnvDiff {
+            kind: DiffKind::Changed,
+            current: Some("mine".to_string()),
+            ..golden[1].clone()
+        };
+        assert_eq!(diff(&env_of(&input), &expected_env(PORT)), golden);
+
+        let (_, input, _) = golden!("non-object-env");
+        let input = object(serde_json::from_str(input).unwrap());
+        assert_eq!(diff(&env_of(&input), &expected_env(PORT)), all_added());
+    }
+
+    #[test]
+    fn missing_file_reads_as_empty_and_repairs() {
+        let (path, backups) = scratch("missing");
+        let status = check(&path, PORT);
+        assert!(!status.healthy);
+        assert_eq!(status.diff, all_added());
+
+        let status = repair(&path, &backups, PORT, None).unwrap();
+        assert!(status.healthy);
+        let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, json!({ "env": proxy_env() }));
+        assert!(list_backups(&backups).is_empty());
+    }
+
+    #[test]
+    fn invalid_json_is_never_overwritten() {
+        let (path, backups) = scratch("invalid");
+        fs::write(&path, "{ not json").unwrap();
+        let status = check(&path, PORT);
+        assert!(!status.healthy);
+        assert_eq!(status.current, json!({}));
+        assert_eq!(status.diff, all_added());
+
+        let err = repair(&path, &backups, PORT, None).unwrap_err();
+        assert!(err.contains("is not valid JSON"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{ not json");
+        assert!(list_backups(&backups).is_empty());
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_backups() {
+        let (_, backups) = scratch("prune");
+        for i in 0..13 {
+            back_up(&backups, &json!({ "i": i }).to_string()).unwrap();
+        }
+        let found = list_backups(&backups);
+        assert_eq!(found.len(), MAX_BACKUPS);
+        let contents: Vec<_> = found
+            .iter()
+            .map(|backup| fs::read_to_string(&backup.path).unwrap())
+            .collect();
+        assert_eq!(contents.first().unwrap(), r#"{"i":12}"#);
+        assert_eq!(contents.last().unwrap(), r#"{"i":3}"#);
+        assert_eq!(backup_seq(&found[0].name), 13);
+        assert_eq!(backup_seq("settings.20240102-030405.json"), 0);
+    }
+
+    #[test]
+    fn unchanged_secrets_stay_masked() {
+        let input = object(json!({ "env": proxy_env() }));
+        let diff = diff(&env_of(&input), &expected_env(PORT));
+        assert!(diff.iter().all(|entry| entry.kind == DiffKind::Unchanged));
+        assert_eq!(diff[0].current.as_deref(), Some("••••"));
+        assert_eq!(mask("sk-ant-0123456789"), "••••89");
+    }
+
+    #[test]
+    fn a_concurrent_edit_fails_the_write() {
+        let (path, backups) = scratch("conflict");
+        fs::write(&path, r#"{"edited":"by claude"}"#).unwrap();
+        let err = replace(&path, &backups, Some(r#"{"read":"earlier"}"#), "{}").unwrap_err();
+        assert!(err.contains("changed while it was being updated"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"edited":"by claude"}"#);
+        let left: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect();
+        assert!(left.is_empty(), "{left:?}");
+    }
+
+    #[test]
+    fn every_write_is_backed_up_first() {
+        let (path, backups) = scratch("backups");
+        repair(&path, &backups, PORT, None).unwrap();
+        assert!(list_backups(&backups).is_empty(), "nothing to back up yet");
+
+        fs::write(&path, r#"{"v":1}"#).unwrap();
+        repair(&path, &backups, PORT, None).unwrap();
+        let first = fs::read_to_string(&path).unwrap();
+        repair(&path, &backups, 9090, None).unwrap();
+
+        let found = list_backups(&backups);
+        let names: Vec<_> = found.iter().map(|backup| backup.name.as_str()).collect();
+        assert_eq!(names.len(), 2);
+        for name in &names {
+            let stamp = name.trim_start_matches("settings.").split('.').next().unwrap();
+            assert!(NaiveDateTime::parse_from_str(stamp, "%Y%m%d-%H%M%S").is_ok(), "{name}");
+        }
+        assert!(names[0].ends_with(".000002.json"), "{names:?}");
+        assert!(names[1].ends_with(".000001.json"), "{names:?}");
+        assert_eq!(fs::read_to_string(&found[0].path).unwrap(), first);
+        assert_eq!(fs::read_to_string(&found[1].path).unwrap(), r#"{"v":1}"#);
+        assert_eq!(found[1].size, 7);
+    }
+
+    #[test]
+    fn restore_puts_back_the_exact_bytes() {
+        let (path, backups) = scratch("restore");
+        let original = "{\n\t\"model\": \"opus\",  \"env\": {}\n}\n";
+        fs::write(&path, original).unwrap();
+        repair(&path, &backups, PORT, None).unwrap();
+        let repaired = fs::read_to_string(&path).unwrap();
+        let name = list_backups(&backups)[0].name.clone();
+
+        let status = restore(&path, &backups, &name, PORT).unwrap();
+        assert!(!status.healthy);
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        // The repaired file it replaced is kept too.
+        let newest = &list_backups(&backups)[0];
+        assert_ne!(newest.name, name);
+        assert_eq!(fs::read_to_string(&newest.path).unwrap(), repaired);
+    }
+
+    #[test]
+    fn restore_rejects_an_unknown_backup() {
+        let (path, backups) = scratch("restore-unknown");
+        fs::write(&path, r#"{"v":1}"#).unwrap();
+        repair(&path, &backups, PORT, None).unwrap();
+        let before = fs::read_to_string(&path).unwrap();
+
+        let err = restore(&path, &backups, "settings.nope.json", PORT).unwrap_err();
+        assert_eq!(err, "No backup named settings.nope.json");
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        assert_eq!(list_backups(&backups).len(), 1);
+    }
+
+    #[test]
+    fn detach_puts_back_what_attach_replaced() {
+        let (path, backups) = scratch("detach");
+        let before = json!({ "env": { "ANTHROPIC_MODEL": "mine", "HTTP_PROXY": "http://corp:3128" } });
+        fs::write(&path, before.to_string()).unwrap();
+        repair(&path, &backups, PORT, None).unwrap();
+        assert_eq!(attached_keys(&backups).len(), 7);
+
+        let status = detach(&path, &backups, PORT).unwrap();
+        let after = read_settings(&path).unwrap();
+        assert_eq!(Value::Object(after), before, "restored, added keys removed");
+        assert!(!status.healthy);
+        assert!(attached_keys(&backups).is_empty());
+    }
+
+    #[test]
+    fn detach_leaves_keys_the_user_changed() {
+        let (path, backups) = scratch("detach-edited");
+        repair(&path, &backups, PORT, None).unwrap();
+        let mut edited = read_settings(&path).unwrap();
+        edited["env"]["ANTHROPIC_BASE_URL"] = json!("http://elsewhere:1234");
+        fs::write(&path, Value::Object(edited).to_string()).unwrap();
+
+        detach(&path, &backups, PORT).unwrap();
+        let after = read_settings(&path).unwrap();
+        assert_eq!(after["env"], json!({ "ANTHROPIC_BASE_URL": "http://elsewhere:1234" }));
+    }
+
+    #[test]
+    fn detach_without_a_record_touches_nothing() {
+        let (path, backups) = scratch("detach-none");
+        let before = json!({ "env": proxy_env() }).to_string();
+        fs::write(&path, &before).unwrap();
+
+        let err = detach(&path, &backups, PORT).unwrap_err();
+        assert!(err.contains("has no record of attaching"), "{err}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        assert!(list_backups(&backups).is_empty());
+    }
+
+    #[test]
+    fn reattach_request_lasts_until_the_next_attach() {
+        let (path, backups) = scratch("reattach");
+        let keys = vec!["ANTHROPIC_MODEL".to_string(), "ANTHROPIC_BASE_URL".to_string()];
+        assert_eq!(reattach_requested(&backups), None);
+        request_reattach(&backups, keys.clone()).unwrap();
+        assert_eq!(reattach_requested(&backups), Some(keys.clone()));
+        assert!(attached_keys(&backups).is_empty());
+
+        repair(&path, &backups, PORT, Some(&keys)).unwrap();
+        assert_eq!(reattach_requested(&backups), None);
+        assert_eq!(attached_keys(&backups), keys);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex as StdMutex,
+    },
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+use tokio::{
+    io::AsyncWriteExt,
+    process::ChildStdin,
+    sync::{oneshot, Mutex},
+    time::timeout,
+};
+
+type Reply = Result<Value, String>;
+
+/// JSON-lines command channel on the daemon's stdin. Requests carry an `id`;
+/// the daemon answers with a `response` event on stdout carrying the same id,
+/// which the line reader hands back through [`ControlChannel::resolve`].
+#[derive(Default)]
+pub struct ControlChannel {
+    stdin: Mutex<Option<ChildStdin>>,
+    pending: StdMutex<HashMap<u64, oneshot::Sender<Reply>>>,
+    next_id: AtomicU64,
+}
+
+impl ControlChannel {
+    pub async fn attach(&self, stdin: ChildStdin) {
+        *self.stdin.lock().await = Some(stdin);
+    }
+
+    /// Closes stdin and fails every request still waiting for an answer.
+    pub async fn detach(&self) {
+        self.stdin.lock().await.take();
+        let waiting: Vec<_> = match self.pending.lock() {
+            Ok(mut pending) => pending.drain().map(|(_, tx)| tx).collect(),
+            Err(_) => Vec::new(),
+        };
+        for tx in waiting {
+            let _ = tx.send(Err("Proxy stopped before answering".to_string()));
+        }
+    }
+
+    pub async fn request(&self, command: &str, args: Value, deadline: Duration) -> Reply {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (tx, rx) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id, tx);
+        }
+
+        let mut line = json!({ "id": id, "command": command, "args": args }).to_string();
+        line.push('\n');
+
+        let written = {
+            let mut guard = self.stdin.lock().await;
+            match guard.as_mut() {
+                Some(stdin) => match stdin.write_all(line.as_bytes()).await {
+                    Ok(()) => stdin.flush().await.map_err(|err| err.to_string()),
+                    Err(err) => Err(err.to_string()),
+                },
+                None => Err("Proxy is not running".to_string()),
+            }
+        };
+        if let Err(err) = written {
+            self.forget(id);
+            return Err(err);
+        }
+
+        match timeout(deadline, rx).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => Err("Proxy stopped before answering".to_string()),
+            Err(_) => {
+                self.forget(id);
+                Err(format!(
+                    "Proxy did not answer '{command}' within {}ms",
+                    deadline.as_millis()
+                ))
+            }
+        }
+    }
+
+    /// Delivers a `response` event. Returns false if nobody is waiting for `id`.
+    pub fn resolve(&self, id: u64, reply: Reply) -> bool {
+        let waiting = self
+            .pending
+            .lock()
+            .ok()
+            .and_then(|mut pending| pending.remove(&id));
+        match waiting {
+            Some(tx) => tx.send(reply).is_ok(),
+            None => false,
+        }
+    }
+
+    fn forget(&self, id: u64) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&id);
+        }
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::{process::Stdio, sync::Arc};
+
+    use tokio::{
+        io::{AsyncBufReadExt, BufReader, Lines},
+        process::{Child, ChildStdout, Command},
+    };
+
+    use super::*;
+
+    /// `cat` stands in for the daemon: every request comes straight back on
+    /// its stdout, where the test reads the id.
+    async fn echo() -> (Arc<ControlChannel>, Child, Lines<BufReader<ChildStdout>>) {
+        let mut child = Command::new("cat")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let channel = Arc::new(ControlChannel::default());
+        channel.attach(child.stdin.take().unwrap()).await;
+        let lines = BufReader::new(child.stdout.take().unwrap()).lines();
+        (channel, child, lines)
+    }
+
+    async fn next_request(lines: &mut Lines<BufReader<ChildStdout>>) -> (u64, String) {
+        let line = lines.next_line().await.unwrap().unwrap();
+        let request: Value = serde_json::from_str(&line).unwrap();
+        (
+            request["id"].as_u64().unwrap(),
+            request["command"].as_str().unwrap().to_string(),
+        )
+    }
+
+    fn pending(channel: &ControlChannel) -> usize {
+        channel.pending.lock().unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn replies_reach_their_own_request_in_any_order() {
+        let (channel, _child, mut lines) = echo().await;
+        let first = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.request("first", json!({}), Duration::from_secs(5)).await }
+        });
+        let first_id = next_request(&mut lines).await;
+        let second = tokio::spawn({
+            let channel = channel.clone();
+            async move { channel.request("second", json!({}), Duration::from_secs(5)).await }
+        });
+        let second_id = next_request(&mut lines).await;
+        assert_eq!(first_id.1, "first");
+        assert_eq!(second_id.1, "second");
+
+        assert!(!channel.resolve(first_id.0.max(second_id.0) + 1, Ok(json!("stray"))));
+        assert!(channel.resolve(second_id.0, Ok(json!(2))));
+        assert!(channel.resolve(first_id.0, Err("no".to_string())));
+        assert_eq!(second.await.unwrap(), Ok(json!(2)));
+        assert_eq!(first.await.unwrap(), Err("no".to_string()));
+        assert!(!channel.resolve(first_id.0, Ok(json!("again"))), "already answered");
+        assert_eq!(pending(&channel), 0);
+    }
+
+    #[tokio::test]
+    async fn a_request_times_out_and_forgets_its_id() {
+        let (channel, _child, mut lines) = echo().await;
+        let reply = channel
+            .request("listAccounts", json!({}), Duration::from_millis(50))
+            .await;
+        assert_eq!(reply, Err("Proxy did not answer 'listAccounts' within 50ms".to_string()));
+        assert_eq!(pending(&channel), 0);
+        let (id, _) = next_request(&mut lines).await;
+        assert!(!channel.resolve(id, Ok(json!("late"))));
+    }
+
+    #[tokio::test]
+    async fn detach_fails_every_waiting_request() {
+        let (channel, _child, mut lines) = echo().await;
+        let waiting: Vec<_> = (0..3)
+            .map(|_| {
+                let channel = channel.clone();
+                tokio::spawn(async move { channel.request("x", json!({}), Duration::from_secs(5)).await })
+            })
+            .collect();
+        for _ in 0..3 {
+            next_request(&mut lines).await;
+        }
+        channel.detach().await;
+        for request in waiting {
+            assert_eq!(request.await.unwrap(), Err("Proxy stopped before answering".to_string()));
+        }
+        assert_eq!(pending(&channel), 0);
+
+        let reply = channel.request("x", json!({}), Duration::from_secs(5)).await;
+        assert_eq!(reply, Err("Proxy is not running".to_string()));
+        assert_eq!(pending(&channel), 0);
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::Serialize;
+use tar::{Builder, Header};
+
+use crate::{logging, redact::Redactor};
+
+/// How much of each log file goes into a bundle.
+const LOG_TAIL_BYTES: u64 = 512 * 1024;
+
+/// Files collected for a diagnostics archive, all under one top-level
+/// directory named after the time of export.
+pub struct Bundle {
+    root: String,
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Default for Bundle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bundle {
+    pub fn new() -> Self {
+        Self {
+            root: format!("antigravity-diagnostics-{}", Utc::now().format("%Y%m%d-%H%M%S")),
+            entries: Vec::new(),
+        }
+    }
+
+    /// `<root>.tar.gz`, the name offered in the save dialog.
+    pub fn file_name(&self) -> String {
+        format!("{}.tar.gz", self.root)
+    }
+
+    pub fn add(&mut self, name: &str, bytes: Vec<u8>) {
+        self.entries.push((name.to_string(), bytes));
+    }
+
+    /// Pretty-printed JSON, passed through `redactor` like everything else
+    /// in the bundle.
+    pub fn add_json<T: Serialize>(&mut self, name: &str, value: &T, redactor: &Redactor) {
+        let text = serde_json::to_string_pretty(value)
+            .unwrap_or_else(|err| format!("{{\"error\": \"unable to serialize: {err}\"}}"));
+        self.add(name, redactor.redact(&text).into_owned().into_bytes());
+    }
+
+    /// The last `LOG_TAIL_BYTES` of the live log and of each rotated copy,
+    /// decompressed, under `logs/`.
+    pub fn add_log_tails(&mut self, current: &Path, redactor: &Redactor) {
+        for file in logging::list_logs(current) {
+            let name = file.name.trim_end_matches(".gz").to_string();
+            let bytes = match tail_bytes(&file.path, file.compressed) {
+                Ok(bytes) => bytes,
+                Err(err) => format!("unable to read {}: {err}\n", file.path.display()).into_bytes(),
+            };
+            let text = String::from_utf8_lossy(&bytes);
+            let redacted: String = text
+                .lines()
+                .map(|line| redactor.redact(line) + "\n")
+                .collect();
+            self.add(&format!("logs/{name}"), redacted.into_bytes());
+        }
+    }
+
+    /// Writes the archive to `path` through a temporary file next to it, so a
+    /// failed export never leaves half an archive under the chosen name.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut partial = path.as_os_str().to_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+
+        let result = (|| {
+            let encoder = GzEncoder::new(File::create(&partial)?, Compression::default());
+            let mut archive = Builder::new(encoder);
+            let mtime = Utc::now().timestamp().max(0) as u64;
+            for (name, bytes) in &self.entries {
+                let mut header = Header::new_gnu();
+                header.set_size(bytes.len() as u64);
+                header.set_mode(0o600);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                archive.append_data(&mut header, format!("{}/{name}", self.root), bytes.as_slice())?;
+            }
+            archive.into_inner()?.finish()?.sync_all()?;
+            fs::rename(&partial, path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        result
+    }
+}
+
+fn tail_bytes(path: &Path, compressed: bool) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    if compressed {
+        GzDecoder::new(File::open(path)?).read_to_end(&mut bytes)?;
+        let cut = bytes.len().saturating_sub(LOG_TAIL_BYTES as usize);
+        bytes.drain(..cut);
+    } else {
+        let mut file = File::open(path)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        file.seek(SeekFrom::Start(len.saturating_sub(LOG_TAIL_BYTES)))?;
+        file.read_to_end(&mut bytes)?;
+    }
+    // Drop the partial first line left by cutting mid-file.
+    if bytes.len() as u64 >= LOG_TAIL_BYTES {
+        if let Some(newline) = bytes.iter().position(|byte| *byte == b'\n') {
+            bytes.drain(..=newline);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, io::Write};
+
+    use serde_json::json;
+    use tar::Archive;
+
+    use super::*;
+    use crate::settings::RedactSettings;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("diagnostics-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Entry name, minus the root directory, to contents.
+    fn unpack(path: &Path) -> BTreeMap<String, String> {
+        let mut archive = Archive::new(GzDecoder::new(File::open(path).unwrap()));
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let name = entry.path().unwrap().display().to_string();
+                let mut text = String::new();
+                entry.read_to_string(&mut text).unwrap();
+                let (root, name) = name.split_once('/').unwrap();
+                assert!(root.starts_with("antigravity-diagnostics-"), "{root}");
+                (name.to_string(), text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_bundle_holds_redacted_json_and_log_tails() {
+        let dir = scratch("bundle");
+        let log = dir.join("desktop.log");
+        fs::write(
+            &log,
+            "[t] [INFO] switched to jane.doe@example.com\n[t] [INFO] Authorization: Bearer abc.def-123==\n",
+        )
+        .unwrap();
+        // A gzip-rotated log longer than the tail, so its first line is cut.
+        let mut rotated = GzEncoder::new(
+            File::create(dir.join("desktop.20240102-030405.log.gz")).unwrap(),
+            Compression::default(),
+        );
+        writeln!(rotated, "first line").unwrap();
+        for i in 0..LOG_TAIL_BYTES / 32 {
+            writeln!(rotated, "[t] [STDOUT] request {i:>10} ok......").unwrap();
+        }
+        writeln!(rotated, "last line refresh 1//0gAbCdEfGhIjKlMnOpQrStUv").unwrap();
+        rotated.finish().unwrap();
+
+        let redactor = Redactor::new(&RedactSettings::default()).0;
+        let mut bundle = Bundle::default();
+        bundle.add_json(
+            "status.json",
+            &json!({ "account": "jane.doe@example.com", "refresh_token": "1//0gAbCdEfGhIjKlMnOpQrStUv" }),
+            &redactor,
+        );
+        bundle.add_log_tails(&log, &redactor);
+        let out = dir.join(bundle.file_name());
+        bundle.write(&out).unwrap();
+
+        let entries = unpack(&out);
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            ["logs/desktop.20240102-030405.log", "logs/desktop.log", "status.json"]
+        );
+        for (name, text) in &entries {
+            for secret in ["jane.doe", "abc.def-123", "1//0gAbCdEfGhIjKlMnOpQrStUv"] {
+                assert!(!text.contains(secret), "{secret} survived in {name}");
+            }
+        }
+        assert!(entries["status.json"].contains("j***@example.com"));
+        assert!(entries["logs/desktop.log"].contains("switched to j***@example.com"));
+
+        let tail = &entries["logs/desktop.20240102-030405.log"];
+        assert!(tail.len() as u64 <= LOG_TAIL_BYTES);
+        assert!(tail.starts_with("[t] [STDOUT] request"), "cut at a line boundary");
+        assert!(!tail.contains("first line"));
+        assert!(tail.ends_with("last line refresh [REDACTED]\n"), "{}", &tail[tail.len() - 80..]);
+
+        let mut partial = out.clone().into_os_string();
+        partial.push(".partial");
+        assert!(!Path::new(&partial).exists());
+    }
+}
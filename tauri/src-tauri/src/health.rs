@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+/// Where the daemon is in its startup, as seen by the shell.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "phase", content = "reason", rename_all = "snake_case")]
+pub enum Readiness {
+    #[default]
+    Stopped,
+    /// Spawned, waiting for `started` and a healthy `/health`.
+    Starting,
+    Ready,
+    /// Running, but `/health` is failing or no account can serve requests.
+    Degraded(String),
+}
+
+/// Account counts reported by `GET /health`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HealthCounts {
+    pub total: u64,
+    pub available: u64,
+    pub rate_limited: u64,
+    pub invalid: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub status_code: u16,
+    pub healthy: bool,
+    pub counts: HealthCounts,
+    pub error: Option<String>,
+}
+
+impl HealthReport {
+    pub fn readiness(&self) -> Readiness {
+        if !self.healthy {
+            let reason = self
+                .error
+                .clone()
+                .unwrap_or_else(|| format!("/health answered HTTP {}", self.status_code));
+            return Readiness::Degraded(reason);
+        }
+        if self.counts.total == 0 {
+            return Readiness::Degraded("No accounts configured".to_string());
+        }
+        if self.counts.available == 0 {
+            return Readiness::Degraded(format!(
+                "No account available ({} rate-limited, {} invalid)",
+                self.counts.rate_limited, self.counts.invalid
+            ));
+        }
+        Readiness::Ready
+    }
+}
+
+/// Issues `GET /health` against the proxy on localhost. Kept to a bare HTTP/1.1
+/// request so the shell does not need an HTTP client for one local probe.
+pub async fn probe(port: u16, deadline: Duration) -> Result<HealthReport, String> {
+    let request = async {
+        let mut stream = TcpStream::connect(("127.0.0.1", port))
+            .await
+            .map_err(|err| err.to_string())?;
+        let head = format!(
+            "GET /health HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\nAccept: application/json\r\nConnection: close\r\n\r\n"
+        );
+        stream
+            .write_all(head.as_bytes())
+            .await
+            .map_err(|err| err.to_string())?;
+        let mut raw = Vec::new();
+        stream
+            .read_to_end(&mut raw)
+            .await
+            .map_err(|err| err.to_string())?;
+        parse_response(&raw)
+    };
+
+    timeout(deadline, request)
+        .await
+        .map_err(|_| format!("timed out after {}ms", deadline.as_millis()))?
+}
+
+fn parse_response(raw: &[u8]) -> Result<HealthReport, String> {
+    let text = String::from_utf8_lossy(raw);
+    let (head, body) = text
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| "Malformed HTTP response".to_string())?;
+    let status_code = head
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| "Missing HTTP status".to_string())?;
+    let json: Value = serde_json::from_str(body.trim())
+        .map_err(|err| format!("Invalid /health body: {err}"))?;
+
+    let count = |key: &str| json.get(key).and_then(Value::as_u64).unwrap_or(0);
+    Ok(HealthReport {
+        status_code,
+        healthy: status_code == 200 && json.get("status").and_then(Value::as_str) == Some("ok"),
+        counts: HealthCounts {
+            total: count("totalAccounts"),
+            available: count("available"),
+            rate_limited: count("rateLimited"),
+            invalid: count("invalid"),
+        },
+        error: json.get("error").and_then(Value::as_str).map(str::to_string),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: &str, body: &str) -> Vec<u8> {
+        format!("HTTP/1.1 {status}\r\nContent-Type: application/json\r\n\r\n{body}").into_bytes()
+    }
+
+    #[test]
+    fn healthy_response() {
+        let body = r#"{"status":"ok","totalAccounts":3,"available":1,"rateLimited":1,"invalid":1}"#;
+        let report = parse_response(&response("200 OK", body)).unwrap();
+        assert!(report.healthy);
+        assert_eq!(
+            report.counts,
+            HealthCounts {
+                total: 3,
+                available: 1,
+                rate_limited: 1,
+                invalid: 1
+            }
+        );
+        assert_eq!(report.readiness(), Readiness::Ready);
+    }
+
+    #[test]
+    fn unhealthy_responses_are_degraded() {
+        let body = r#"{"status":"error","error":"boom"}"#;
+        let report = parse_response(&response("503 Service Unavailable", body)).unwrap();
+        assert_eq!(report.status_code, 503);
+        assert_eq!(report.readiness(), Readiness::Degraded("boom".to_string()));
+
+        let report = parse_response(&response("500 Internal Server Error", "{}")).unwrap();
+        assert_eq!(report.readiness(), Readiness::Degraded("/health answered HTTP 500".to_string()));
+
+        let report = parse_response(&response("200 OK", r#"{"status":"ok","totalAccounts":0}"#)).unwrap();
+        assert_eq!(report.readiness(), Readiness::Degraded("No accounts configured".to_string()));
+
+        let body = r#"{"status":"ok","totalAccounts":2,"available":0,"rateLimited":1,"invalid":1}"#;
+        let report = parse_response(&response("200 OK", body)).unwrap();
+        assert_eq!(
+            report.readiness(),
+            Readiness::Degraded("No account available (1 rate-limited, 1 invalid)".to_string())
+        );
+    }
+
+    #[test]
+    fn malformed_responses() {
+        assert_eq!(parse_response(b"HTTP/1.1 200 OK").unwrap_err(), "Malformed HTTP response");
+        assert_eq!(parse_response(b"garbage\r\n\r\n{}").unwrap_err(), "Missing HTTP status");
+        let err = parse_response(&response("200 OK", "<html>")).unwrap_err();
+        assert!(err.starts_with("Invalid /health body"), "{err}");
+    }
+}
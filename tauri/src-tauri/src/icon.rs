@@ -0,0 +1,404 @@
+use std::sync::OnceLock;
+
+/// Rendered at the largest size the tray is likely to ask for and scaled down.
+const BASE_PNG: &[u8] = include_bytes!("../icons/128x128.png");
+
+/// Nominal tray icon size in points; multiplied by the display scale.
+pub const TRAY_POINTS: f64 = 22.0;
+
+/// Samples per axis when estimating how much of a pixel a shape covers.
+const SUPERSAMPLE: u32 = 4;
+
+/// 3×5 digits for badges, one row per byte, high bit on the left.
+const GLYPHS: [[u8; 5]; 11] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b111, 0b001, 0b111, 0b100, 0b111], // 2
+    [0b111, 0b001, 0b111, 0b001, 0b111], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b111, 0b001, 0b111], // 5
+    [0b111, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b010, 0b010, 0b010], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b111], // 9
+    [0b000, 0b010, 0b111, 0b010, 0b000], // +
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayVisual {
+    Running,
+    Warning,
+    Stopped,
+}
+
+impl TrayVisual {
+    fn color(self) -> [u8; 3] {
+        match self {
+            TrayVisual::Running => [16, 185, 129],
+            TrayVisual::Warning => [251, 191, 36],
+            TrayVisual::Stopped => [239, 68, 68],
+        }
+    }
+}
+
+/// Everything that decides what the tray icon looks like, so `update_tray`
+/// can skip re-rendering when nothing changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IconSpec {
+    pub visual: TrayVisual,
+    /// Drawn as a pill in the top-right corner; `None` or 0 draws nothing.
+    pub badge: Option<u32>,
+    /// Side of the square icon in physical pixels.
+    pub size: u32,
+    /// Monochrome output for macOS template icons: the system recolors it,
+    /// so the status shows as the dot's shape (hollow when stopped).
+    pub template: bool,
+}
+
+/// A straight-alpha RGBA8 image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Raster {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            rgba: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    pub fn decode_png(bytes: &[u8]) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8() | png::Transformations::ALPHA);
+        let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(|err| err.to_string())?;
+        buffer.truncate(info.buffer_size());
+        let rgba = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|px| [px[0], px[0], px[0], px[1]])
+                .collect(),
+            other => return Err(format!("unexpected PNG color type {other:?}")),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            rgba,
+        })
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [self.rgba[i], self.rgba[i + 1], self.rgba[i + 2], self.rgba[i + 3]]
+    }
+
+    /// Box-filters down (or samples up) to `size`×`size`, averaging in
+    /// premultiplied alpha so transparent edges don't darken.
+    pub fn resize(&self, size: u32) -> Self {
+        let mut out = Self::new(size, size);
+        let sx = self.width as f64 / size as f64;
+        let sy = self.height as f64 / size as f64;
+        for y in 0..size {
+            let y0 = (y as f64 * sy) as u32;
+            let y1 = (((y + 1) as f64 * sy).ceil() as u32).clamp(y0 + 1, self.height);
+            for x in 0..size {
+                let x0 = (x as f64 * sx) as u32;
+                let x1 = (((x + 1) as f64 * sx).ceil() as u32).clamp(x0 + 1, self.width);
+                let mut sum = [0f64; 4];
+                for yy in y0..y1 {
+                    for xx in x0..x1 {
+                        let [r, g, b, a] = self.pixel(xx, yy);
+                        let a = a as f64 / 255.0;
+                        sum[0] += r as f64 * a;
+                        sum[1] += g as f64 * a;
+                        sum[2] += b as f64 * a;
+                        sum[3] += a;
+                    }
+                }
+                let count = ((x1 - x0) * (y1 - y0)) as f64;
+                let alpha = sum[3] / count;
+                let color = |channel: f64| {
+                    if sum[3] > 0.0 {
+                        (channel / sum[3]).round() as u8
+                    } else {
+                        0
+                    }
+                };
+                out.put(x, y, [color(sum[0]), color(sum[1]), color(sum[2]), (alpha * 255.0).round() as u8]);
+            }
+        }
+        out
+    }
+
+    fn put(&mut self, x: u32, y: u32, px: [u8; 4]) {
+        let i = ((y * self.width + x) * 4) as usize;
+        self.rgba[i..i + 4].copy_from_slice(&px);
+    }
+
+    /// Source-over blend of `color` at `coverage` (0..=1).
+    fn blend(&mut self, x: u32, y: u32, color: [u8; 3], coverage: f64) {
+        if coverage <= 0.0 {
+            return;
+        }
+        let [r, g, b, a] = self.pixel(x, y);
+        let dst_a = a as f64 / 255.0;
+        let out_a = coverage + dst_a * (1.0 - coverage);
+        let mix = |src: u8, dst: u8| {
+            ((src as f64 * coverage + dst as f64 * dst_a * (1.0 - coverage)) / out_a).round() as u8
+        };
+        self.put(
+            x,
+            y,
+            [mix(color[0], r), mix(color[1], g), mix(color[2], b), (out_a * 255.0).round() as u8],
+        );
+    }
+
+    /// Scales alpha by `1 - coverage`, punching a hole.
+    fn erase(&mut self, x: u32, y: u32, coverage: f64) {
+        let i = ((y * self.width + x) * 4 + 3) as usize;
+        self.rgba[i] = (self.rgba[i] as f64 * (1.0 - coverage.clamp(0.0, 1.0))).round() as u8;
+    }
+
+    /// Runs `paint(x, y, coverage)` over every pixel `inside` touches.
+    fn cover(&mut self, inside: impl Fn(f64, f64) -> bool, mut paint: impl FnMut(&mut Self, u32, u32, f64)) {
+        let step = 1.0 / SUPERSAMPLE as f64;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut hits = 0;
+                for sy in 0..SUPERSAMPLE {
+                    for sx in 0..SUPERSAMPLE {
+                        let px = x as f64 + (sx as f64 + 0.5) * step;
+                        let py = y as f64 + (sy as f64 + 0.5) * step;
+                        if inside(px, py) {
+                            hits += 1;
+                        }
+                    }
+                }
+                if hits > 0 {
+                    paint(self, x, y, hits as f64 / (SUPERSAMPLE * SUPERSAMPLE) as f64);
+                }
+            }
+        }
+    }
+
+    fn fill_circle(&mut self, cx: f64, cy: f64, radius: f64, color: [u8; 3]) {
+        let inside = |x: f64, y: f64| (x - cx).powi(2) + (y - cy).powi(2) <= radius * radius;
+        self.cover(inside, |raster, x, y, coverage| raster.blend(x, y, color, coverage));
+    }
+
+    fn erase_circle(&mut self, cx: f64, cy: f64, radius: f64) {
+        let inside = |x: f64, y: f64| (x - cx).powi(2) + (y - cy).powi(2) <= radius * radius;
+        self.cover(inside, |raster, x, y, coverage| raster.erase(x, y, coverage));
+    }
+}
+
+/// The app icon from `icons/`, decoded once.
+fn base() -> &'static Raster {
+    static BASE: OnceLock<Raster> = OnceLock::new();
+    BASE.get_or_init(|| Raster::decode_png(BASE_PNG).expect("bundled tray icon"))
+}
+
+/// The app icon with a status dot in the bottom-right corner and, if
+/// `spec.badge` is set, a count in the top-right.
+pub fn render(spec: &IconSpec) -> Raster {
+    let size = spec.size.max(8);
+    let mut icon = base().resize(size);
+    if spec.template {
+        // Keep the silhouette; the system supplies the color.
+        for px in icon.rgba.chunks_exact_mut(4) {
+            px[..3].fill(0);
+        }
+    }
+    let ink = if spec.template { [0, 0, 0] } else { spec.visual.color() };
+
+    let s = size as f64;
+    let radius = s * 0.2;
+    let (cx, cy) = (s - radius - s * 0.02, s - radius - s * 0.02);
+    // A transparent gap around the dot keeps it legible over the artwork.
+    icon.erase_circle(cx, cy, radius + s * 0.07);
+    icon.fill_circle(cx, cy, radius, ink);
+    if spec.template && spec.visual == TrayVisual::Stopped {
+        icon.erase_circle(cx, cy, radius * 0.55);
+    }
+
+    if let Some(count) = spec.badge.filter(|count| *count > 0) {
+        draw_badge(&mut icon, count, spec.template);
+    }
+    icon
+}
+
+/// A pill sized to the digits: `1`…`9`, then `9+`.
+fn draw_badge(icon: &mut Raster, count: u32, template: bool) {
+    let text: Vec<usize> = if count > 9 { vec![9, 10] } else { vec![count as usize] };
+    let s = icon.width as f64;
+    let scale = ((s * 0.45 / 7.0).floor() as u32).max(1);
+    let glyph_w = 3 * scale;
+    let text_w = text.len() as u32 * glyph_w + (text.len() as u32 - 1) * scale;
+    let height = 7 * scale;
+    let width = (text_w + 4 * scale).max(height);
+    let right = icon.width;
+    let left = right.saturating_sub(width);
+
+    let (l, r, b) = (left as f64, right as f64, height as f64);
+    let gap = scale as f64;
+    icon.cover(pill(l - gap, -gap, r + gap, b + gap), |raster, x, y, coverage| {
+        raster.erase(x, y, coverage)
+    });
+    let fill = if template { [0, 0, 0] } else { [220, 38, 38] };
+    icon.cover(pill(l, 0.0, r, b), |raster, x, y, coverage| raster.blend(x, y, fill, coverage));
+
+    // Digits are white on a colored pill, or cut out of a template pill.
+    let mut pen_x = left + (width - text_w) / 2;
+    let pen_y = scale;
+    for glyph in text {
+        for (row, bits) in GLYPHS[glyph].iter().enumerate() {
+            for col in 0..3u32 {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        let x = pen_x + col * scale + dx;
+                        let y = pen_y + row as u32 * scale + dy;
+                        if x >= icon.width || y >= icon.height {
+                            continue;
+                        }
+                        if template {
+                            icon.erase(x, y, 1.0);
+                        } else {
+                            icon.put(x, y, [255, 255, 255, 255]);
+                        }
+                    }
+                }
+            }
+        }
+        pen_x += glyph_w + scale;
+    }
+}
+
+/// A rectangle with fully rounded ends, from `left`/`top` to `right`/`bottom`.
+fn pill(left: f64, top: f64, right: f64, bottom: f64) -> impl Fn(f64, f64) -> bool {
+    let radius = (bottom - top) / 2.0;
+    move |x: f64, y: f64| {
+        let cy = top + radius;
+        let cx = x.clamp(left + radius, (right - radius).max(left + radius));
+        (x - cx).powi(2) + (y - cy).powi(2) <= radius * radius
+    }
+}
+
+/// Physical pixel size for a display scale factor.
+pub fn size_for_scale(scale: f64) -> u32 {
+    (TRAY_POINTS * scale.clamp(1.0, 4.0)).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [220, 38, 38, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    fn spec(visual: TrayVisual, badge: Option<u32>, template: bool) -> IconSpec {
+        IconSpec {
+            visual,
+            badge,
+            size: 22,
+            template,
+        }
+    }
+
+    /// At 22px the badge uses 1px glyph cells: a 7px pill at x 15..22 with a
+    /// single digit drawn from (17, 1).
+    fn digit_cells(glyph: usize) -> Vec<bool> {
+        (0..5)
+            .flat_map(|row| (0..3).map(move |col| (row, col)))
+            .map(|(row, col)| GLYPHS[glyph][row] & (0b100 >> col) != 0)
+            .collect()
+    }
+
+    #[test]
+    fn size_follows_the_display_scale() {
+        assert_eq!(size_for_scale(1.0), 22);
+        assert_eq!(size_for_scale(2.0), 44);
+        assert_eq!(size_for_scale(3.0), 66);
+        assert_eq!(size_for_scale(0.5), 22);
+        assert_eq!(size_for_scale(8.0), 88);
+    }
+
+    #[test]
+    fn render_is_square_at_the_requested_size() {
+        for size in [22, 44, 66] {
+            let icon = render(&IconSpec {
+                size,
+                ..spec(TrayVisual::Running, Some(3), false)
+            });
+            assert_eq!((icon.width, icon.height), (size, size));
+            assert_eq!(icon.rgba.len(), (size * size * 4) as usize);
+        }
+    }
+
+    #[test]
+    fn status_dot_is_opaque_in_the_state_color() {
+        for visual in [TrayVisual::Running, TrayVisual::Warning, TrayVisual::Stopped] {
+            let icon = render(&spec(visual, None, false));
+            let [r, g, b] = visual.color();
+            assert_eq!(icon.pixel(17, 17), [r, g, b, 255], "{visual:?}");
+        }
+    }
+
+    #[test]
+    fn badge_draws_the_digit_on_a_pill() {
+        for visual in [TrayVisual::Running, TrayVisual::Warning, TrayVisual::Stopped] {
+            let icon = render(&spec(visual, Some(3), false));
+            for (i, lit) in digit_cells(3).into_iter().enumerate() {
+                let (x, y) = (17 + i as u32 % 3, 1 + i as u32 / 3);
+                let expected = if lit { WHITE } else { RED };
+                assert_eq!(icon.pixel(x, y), expected, "{visual:?} at ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn zero_badge_draws_nothing() {
+        let none = render(&spec(TrayVisual::Warning, None, false));
+        assert_eq!(render(&spec(TrayVisual::Warning, Some(0), false)), none);
+        assert_ne!(render(&spec(TrayVisual::Warning, Some(1), false)), none);
+    }
+
+    #[test]
+    fn template_output_is_monochrome() {
+        for visual in [TrayVisual::Running, TrayVisual::Warning, TrayVisual::Stopped] {
+            let icon = render(&spec(visual, Some(3), true));
+            assert!(icon.rgba.chunks_exact(4).all(|px| px[..3] == [0, 0, 0]), "{visual:?}");
+            // Digits are cut out of a black pill.
+            for (i, lit) in digit_cells(3).into_iter().enumerate() {
+                let (x, y) = (17 + i as u32 % 3, 1 + i as u32 / 3);
+                let alpha = icon.pixel(x, y)[3];
+                assert_eq!(alpha, if lit { 0 } else { 255 }, "{visual:?} at ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn template_shows_stopped_as_a_hollow_dot() {
+        assert_eq!(render(&spec(TrayVisual::Running, None, true)).pixel(17, 17)[3], 255);
+        assert_eq!(render(&spec(TrayVisual::Stopped, None, true)).pixel(17, 17)[3], 0);
+    }
+
+    #[test]
+    fn large_counts_show_nine_plus() {
+        let icon = render(&spec(TrayVisual::Warning, Some(12), false));
+        // "9+" needs a 11px pill; the 9 starts at x 13.
+        assert_eq!(icon.pixel(13, 1), WHITE);
+        assert_eq!(icon.pixel(18, 3), WHITE);
+        assert_eq!(icon.pixel(17, 3), WHITE);
+        assert_eq!(icon.pixel(17, 1), RED);
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// What a second launch hands to the running instance before exiting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Forwarded {
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+}
+
+impl Forwarded {
+    fn from_env() -> Self {
+        Self {
+            args: std::env::args().skip(1).collect(),
+            cwd: std::env::current_dir()
+                .ok()
+                .map(|dir| dir.display().to_string()),
+        }
+    }
+}
+
+pub enum Claim {
+    /// No other instance is running. Keep the guard for the app's lifetime.
+    Primary(PrimaryGuard),
+    /// Another instance holds the lock; our arguments were handed to it, or
+    /// the error says why they could not be.
+    Secondary(Result<(), String>),
+}
+
+/// Holds `instance.lock` and listens on `instance.sock` for later launches.
+pub struct PrimaryGuard {
+    #[cfg(not(windows))]
+    lock: nix::fcntl::Flock<fs::File>,
+    #[cfg(not(windows))]
+    listener: std::os::unix::net::UnixListener,
+}
+
+/// Decides whether this process is the only running instance by taking an
+/// exclusive lock in `dir`. If another process holds it, forwards this
+/// launch's arguments over the instance socket instead.
+#[cfg(not(windows))]
+pub fn claim(dir: &Path) -> Result<Claim, String> {
+    use nix::fcntl::{Flock, FlockArg};
+    use std::os::unix::net::UnixListener;
+
+    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    let lock_path = dir.join("instance.lock");
+    let socket_path = dir.join("instance.sock");
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|err| format!("Unable to open {}: {err}", lock_path.display()))?;
+
+    match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+        Ok(lock) => {
+            // Whoever created the socket is gone, since we got the lock.
+            let _ = fs::remove_file(&socket_path);
+            let listener = UnixListener::bind(&socket_path)
+                .map_err(|err| format!("Unable to listen on {}: {err}", socket_path.display()))?;
+            Ok(Claim::Primary(PrimaryGuard { lock, listener }))
+        }
+        Err(_) => Ok(Claim::Secondary(forward(&socket_path))),
+    }
+}
+
+/// Windows has no lock or socket yet; every launch runs as the primary.
+#[cfg(windows)]
+pub fn claim(_dir: &Path) -> Result<Claim, String> {
+    Ok(Claim::Primary(PrimaryGuard {}))
+}
+
+#[cfg(not(windows))]
+fn forward(socket_path: &Path) -> Result<(), String> {
+    use std::{os::unix::net::UnixStream, thread::sleep, time::Duration};
+
+    // The primary may hold the lock but not be listening yet if both
+    // launches raced; give it a moment.
+    let mut attempts = 0;
+    let mut stream = loop {
+        match UnixStream::connect(socket_path) {
+            Ok(stream) => break stream,
+            Err(err) if attempts >= 20 => {
+                return Err(format!("Unable to reach the running instance: {err}"))
+            }
+            Err(_) => {
+                attempts += 1;
+                sleep(Duration::from_millis(100));
+            }
+        }
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+
+    let mut line = serde_json::to_string(&Forwarded::from_env()).map_err(|err| err.to_string())?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|err| err.to_string())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .map_err(|err| err.to_string())?;
+    if reply.trim() == "ok" {
+        Ok(())
+    } else {
+        Err("The running instance did not acknowledge this launch".to_string())
+    }
+}
+
+impl PrimaryGuard {
+    /// Hands every forwarded launch to `on_launch` from a background thread.
+    /// The guard, and with it the lock, lives as long as that thread.
+    #[cfg(not(windows))]
+    pub fn listen<F>(self, on_launch: F)
+    where
+        F: Fn(Forwarded) + Send + 'static,
+    {
+        std::thread::spawn(move || {
+            let PrimaryGuard { lock: _lock, listener } = self;
+            for mut stream in listener.incoming().flatten() {
+                let mut line = String::new();
+                let Ok(reader) = stream.try_clone() else {
+                    continue;
+                };
+                if BufReader::new(reader).read_line(&mut line).is_err() {
+                    continue;
+                }
+                if let Ok(forwarded) = serde_json::from_str::<Forwarded>(line.trim()) {
+                    let _ = stream.write_all(b"ok\n");
+                    on_launch(forwarded);
+                }
+            }
+        });
+    }
+
+    #[cfg(windows)]
+    pub fn listen<F>(self, _on_launch: F)
+    where
+        F: Fn(Forwarded) + Send + 'static,
+    {
+    }
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::{os::unix::net::UnixListener, path::PathBuf, sync::mpsc, time::Duration};
+
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("instance-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Claims `dir` and reports every launch forwarded to it.
+    fn primary(dir: &Path) -> mpsc::Receiver<Forwarded> {
+        let Ok(Claim::Primary(guard)) = claim(dir) else {
+            panic!("expected to be the primary");
+        };
+        let (tx, rx) = mpsc::channel();
+        guard.listen(move |forwarded| {
+            let _ = tx.send(forwarded);
+        });
+        rx
+    }
+
+    #[test]
+    fn a_second_launch_is_forwarded_to_the_first() {
+        let dir = scratch("forward");
+        let launches = primary(&dir);
+        match claim(&dir) {
+            Ok(Claim::Secondary(Ok(()))) => {}
+            Ok(Claim::Secondary(Err(err))) => panic!("not forwarded: {err}"),
+            _ => panic!("expected to be a secondary"),
+        }
+        let forwarded = launches.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(forwarded.args, std::env::args().skip(1).collect::<Vec<_>>());
+        assert_eq!(
+            forwarded.cwd,
+            Some(std::env::current_dir().unwrap().display().to_string())
+        );
+    }
+
+    #[test]
+    fn a_dead_instance_socket_is_taken_over() {
+        let dir = scratch("stale");
+        fs::create_dir_all(&dir).unwrap();
+        // A crashed instance leaves its socket file with nobody listening.
+        drop(UnixListener::bind(dir.join("instance.sock")).unwrap());
+        fs::write(dir.join("instance.lock"), "").unwrap();
+
+        let launches = primary(&dir);
+        assert!(matches!(claim(&dir), Ok(Claim::Secondary(Ok(())))));
+        assert!(launches.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
@@ -0,0 +1,478 @@
+use std::{
+    cmp::Reverse,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::settings::{LogFormat, LogSettings};
+
+/// Upper bound on how long a written line may sit in the buffer.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+enum Message {
+    Entry(String),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Errors the writer hit on its own file, which therefore cannot be logged
+/// there. Shown in the UI status instead.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LogFailures {
+    pub count: u64,
+    pub last_error: Option<String>,
+    pub last_seen: Option<String>,
+}
+
+/// Appends to `desktop.log` from a dedicated thread so callers never touch the
+/// file. Lines are buffered and flushed once the queue drains (or at least
+/// every second); the file is rotated by size and by UTC day.
+pub struct LogWriter {
+    path: PathBuf,
+    format: LogFormat,
+    tx: mpsc::Sender<Message>,
+    failures: Arc<Mutex<LogFailures>>,
+}
+
+impl LogWriter {
+    pub fn spawn(path: PathBuf, settings: LogSettings) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let format = settings.format;
+        let failures = Arc::new(Mutex::new(LogFailures::default()));
+        let mut sink = Sink {
+            path: path.clone(),
+            settings,
+            file: None,
+            size: 0,
+            opened_on: today(),
+            failures: failures.clone(),
+        };
+        thread::Builder::new()
+            .name("desktop-log".to_string())
+            .spawn(move || sink.run(rx))
+            .expect("failed to spawn the log writer thread");
+        Self {
+            path,
+            format,
+            tx,
+            failures,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn failures(&self) -> LogFailures {
+        self.failures
+            .lock()
+            .map(|failures| failures.clone())
+            .unwrap_or_default()
+    }
+
+    /// Queues one line under the label `append_log` was given (`INFO`,
+    /// `STDOUT`, ...), stamped with the current time.
+    pub fn append(&self, level: &str, line: &str) {
+        let timestamp = Utc::now().to_rfc3339();
+        let entry = match self.format {
+            LogFormat::Text => format!("[{timestamp}] [{level}] {line}\n"),
+            LogFormat::Json => format!("{}\n", json_record(&timestamp, level, line)),
+        };
+        let _ = self.tx.send(Message::Entry(entry));
+    }
+
+    /// Blocks until everything queued so far is on disk, or `deadline` passes.
+    pub fn flush(&self, deadline: Duration) {
+        let (ack_tx, ack_rx) = mpsc::channel();
+        if self.tx.send(Message::Flush(ack_tx)).is_ok() {
+            let _ = ack_rx.recv_timeout(deadline);
+        }
+    }
+}
+
+/// Where a line came from, judged by the label it was logged under.
+fn source_of(level: &str) -> &'static str {
+    match level {
+        "STDOUT" => "daemon-stdout",
+        "STDERR" => "daemon-stderr",
+        _ => "shell",
+    }
+}
+
+/// `{ts, level, source, event, payload}` for daemon events written as JSON on
+/// stdout; `{ts, level, source, event: null, message}` for everything else.
+fn json_record(timestamp: &str, level: &str, line: &str) -> Value {
+    let event = (level == "STDOUT")
+        .then(|| serde_json::from_str::<Value>(line).ok())
+        .flatten()
+        .filter(Value::is_object);
+    match event {
+        Some(payload) => json!({
+            "ts": timestamp,
+            "level": level,
+            "source": source_of(level),
+            "event": payload.get("event").and_then(Value::as_str),
+            "payload": payload,
+        }),
+        None => json!({
+            "ts": timestamp,
+            "level": level,
+            "source": source_of(level),
+            "event": null,
+            "message": line,
+        }),
+    }
+}
+
+struct Sink {
+    path: PathBuf,
+    settings: LogSettings,
+    file: Option<BufWriter<File>>,
+    size: u64,
+    opened_on: NaiveDate,
+    failures: Arc<Mutex<LogFailures>>,
+}
+
+impl Sink {
+    fn run(&mut self, rx: mpsc::Receiver<Message>) {
+        loop {
+            match rx.recv_timeout(FLUSH_INTERVAL) {
+                Ok(message) => {
+                    self.handle(message);
+                    // Drain the burst before paying for a flush.
+                    while let Ok(message) = rx.try_recv() {
+                        self.handle(message);
+                    }
+                    self.flush();
+                }
+                Err(RecvTimeoutError::Timeout) => self.flush(),
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.flush();
+                    break;
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Entry(entry) => {
+                if let Err(err) = self.append(entry.as_bytes()) {
+                    self.fail(format!("Unable to write {}: {err}", self.path.display()));
+                    self.file = None;
+                }
+            }
+            Message::Flush(ack) => {
+                self.flush();
+                let _ = ack.send(());
+            }
+        }
+    }
+
+    fn fail(&self, error: String) {
+        if let Ok(mut failures) = self.failures.lock() {
+            failures.count += 1;
+            failures.last_error = Some(error);
+            failures.last_seen = Some(Utc::now().to_rfc3339());
+        }
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        if self.needs_rotation(bytes.len() as u64) {
+            self.rotate()?;
+        }
+        if let Some(file) = self.file.as_mut() {
+            file.write_all(bytes)?;
+            self.size += bytes.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let metadata = file.metadata()?;
+        self.size = metadata.len();
+        // An existing file belongs to the day it was last written.
+        self.opened_on = metadata
+            .modified()
+            .map(|modified| DateTime::<Utc>::from(modified).date_naive())
+            .unwrap_or_else(|_| today());
+        self.file = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    fn needs_rotation(&self, incoming: u64) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        let too_big = self.settings.max_bytes > 0 && self.size + incoming > self.settings.max_bytes;
+        let new_day = self.settings.daily && self.opened_on != today();
+        too_big || new_day
+    }
+
+    /// Moves the current file aside under a timestamped name, compresses it if
+    /// configured, prunes old files beyond the retention count and reopens.
+    fn rotate(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        let rotated = self.rotated_name();
+        fs::rename(&self.path, &rotated)?;
+        if self.settings.compress {
+            if let Err(err) = compress(&rotated) {
+                self.fail(format!("Unable to compress {}: {err}", rotated.display()));
+            }
+        }
+        self.prune();
+        self.open()
+    }
+
+    fn rotated_name(&self) -> PathBuf {
+        let (stem, ext) = split_name(&self.path);
+        let stamp = Utc::now().format("%Y%m%d-%H%M%S");
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let mut candidate = dir.join(format!("{stem}.{stamp}.{ext}"));
+        let mut counter = 1;
+        while candidate.exists() || with_gz(&candidate).exists() {
+            candidate = dir.join(format!("{stem}.{stamp}-{counter}.{ext}"));
+            counter += 1;
+        }
+        candidate
+    }
+
+    fn prune(&self) {
+        let rotated: Vec<LogFile> = list_logs(&self.path)
+            .into_iter()
+            .filter(|file| !file.current)
+            .collect();
+        for stale in rotated.iter().skip(self.settings.retain) {
+            let _ = fs::remove_file(&stale.path);
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn compress(path: &Path) -> io::Result<()> {
+    let target = with_gz(path);
+    let mut encoder = GzEncoder::new(File::create(&target)?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    fs::remove_file(path)
+}
+
+fn with_gz(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".gz");
+    PathBuf::from(name)
+}
+
+/// `desktop.log` -> (`desktop`, `log`).
+fn split_name(path: &Path) -> (String, String) {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "desktop".to_string());
+    let ext = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "log".to_string());
+    (stem, ext)
+}
+
+/// `desktop.20240102-030405-2.log` -> (`20240102-030405`, 2). Rotated names
+/// embed a sortable UTC timestamp; the counter breaks ties within a second.
+fn rotation_key(name: &str, prefix: &str) -> (String, u32) {
+    let stamp = name
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.split('.').next())
+        .unwrap_or_default();
+    let mut parts = stamp.splitn(3, '-');
+    let date = parts.next().unwrap_or_default();
+    let time = parts.next().unwrap_or_default();
+    let counter = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+    (format!("{date}-{time}"), counter)
+}
+
+fn today() -> NaiveDate {
+    Utc::now().date_naive()
+}
+
+/// One file in the log set: the live log or a rotated copy.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<String>,
+    pub compressed: bool,
+    pub current: bool,
+}
+
+/// The live log followed by its rotated copies, newest first.
+pub fn list_logs(current: &Path) -> Vec<LogFile> {
+    let (stem, ext) = split_name(current);
+    let prefix = format!("{stem}.");
+    let dir = current.parent().unwrap_or_else(|| Path::new("."));
+
+    let describe = |path: PathBuf, current: bool| -> Option<LogFile> {
+        let metadata = fs::metadata(&path).ok()?;
+        let name = path.file_name()?.to_string_lossy().into_owned();
+        Some(LogFile {
+            compressed: name.ends_with(".gz"),
+            modified: metadata
+                .modified()
+                .ok()
+                .map(|time: SystemTime| DateTime::<Utc>::from(time).to_rfc3339()),
+            size: metadata.len(),
+            name,
+            path,
+            current,
+        })
+    };
+
+    let mut rotated: Vec<LogFile> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.as_path() != current)
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .is_some_and(|name| {
+                    name.starts_with(&prefix)
+                        && (name.ends_with(&format!(".{ext}")) || name.ends_with(&format!(".{ext}.gz")))
+                })
+        })
+        .filter_map(|path| describe(path, false))
+        .collect();
+    rotated.sort_by_key(|file| Reverse(rotation_key(&file.name, &prefix)));
+
+    describe(current.to_path_buf(), true)
+        .into_iter()
+        .chain(rotated)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("logging-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("desktop.log")
+    }
+
+    #[test]
+    fn rotated_names_sort_by_stamp_then_counter() {
+        let key = |name| rotation_key(name, "desktop.");
+        assert_eq!(key("desktop.20240102-030405.log"), ("20240102-030405".to_string(), 0));
+        assert_eq!(key("desktop.20240102-030405-2.log.gz"), ("20240102-030405".to_string(), 2));
+        assert!(key("desktop.20240102-030405-10.log") > key("desktop.20240102-030405-9.log"));
+        assert!(key("desktop.20240103-000000.log") > key("desktop.20240102-235959-3.log"));
+    }
+
+    #[test]
+    fn gzip_names_append_to_the_rotated_name() {
+        assert_eq!(
+            with_gz(Path::new("/tmp/desktop.20240102-030405.log")),
+            Path::new("/tmp/desktop.20240102-030405.log.gz")
+        );
+        assert_eq!(split_name(Path::new("/tmp/desktop.log")), ("desktop".into(), "log".into()));
+    }
+
+    #[test]
+    fn json_records_keep_daemon_events() {
+        let event = json_record("ts", "STDOUT", r#"{"event":"status","phase":"started"}"#);
+        assert_eq!(event["source"], "daemon-stdout");
+        assert_eq!(event["event"], "status");
+        assert_eq!(event["payload"]["phase"], "started");
+
+        let text = json_record("ts", "STDERR", "boom");
+        assert_eq!(text["source"], "daemon-stderr");
+        assert_eq!(text["event"], Value::Null);
+        assert_eq!(text["message"], "boom");
+    }
+
+    #[test]
+    fn size_rotation_compresses_and_prunes() {
+        let path = scratch("rotate");
+        let writer = LogWriter::spawn(
+            path.clone(),
+            LogSettings {
+                max_bytes: 200,
+                retain: 2,
+                compress: true,
+                ..LogSettings::default()
+            },
+        );
+        for i in 0..20 {
+            writer.append("INFO", &format!("line {i}"));
+        }
+        writer.flush(Duration::from_secs(5));
+
+        let files = list_logs(&path);
+        assert!(files[0].current);
+        assert!(files[0].size <= 200);
+        let rotated = &files[1..];
+        assert_eq!(rotated.len(), 2);
+        for file in rotated {
+            assert!(file.compressed, "{}", file.name);
+            assert!(file.name.starts_with("desktop.") && file.name.ends_with(".log.gz"));
+            let mut text = String::new();
+            GzDecoder::new(File::open(&file.path).unwrap())
+                .read_to_string(&mut text)
+                .unwrap();
+            assert!(text.lines().all(|line| line.contains("[INFO] line ")), "{text}");
+        }
+        let live = fs::read_to_string(&path).unwrap();
+        assert!(live.ends_with("[INFO] line 19\n"), "{live}");
+    }
+
+    #[test]
+    fn write_failures_are_counted() {
+        let path = scratch("failures");
+        // The log's directory is a file, so nothing can be written.
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let blocked = path.parent().unwrap().join("blocked");
+        fs::write(&blocked, "").unwrap();
+        let writer = LogWriter::spawn(blocked.join("desktop.log"), LogSettings::default());
+        assert_eq!(writer.failures().count, 0);
+
+        writer.append("INFO", "one");
+        writer.append("INFO", "two");
+        writer.flush(Duration::from_secs(5));
+        let failures = writer.failures();
+        assert_eq!(failures.count, 2);
+        let error = failures.last_error.unwrap();
+        assert!(error.starts_with("Unable to write "), "{error}");
+        assert!(failures.last_seen.is_some());
+    }
+}
@@ -0,0 +1,566 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use flate2::read::GzDecoder;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Bytes read per step when walking a log backwards.
+const CHUNK: u64 = 64 * 1024;
+/// Upper bound on bytes examined by one request, so a filter that matches
+/// nothing cannot make a single call scan gigabytes.
+const MAX_SCAN: u64 = 8 * 1024 * 1024;
+/// How much of a line longer than `MAX_SCAN` is shown.
+const MAX_SHOWN: u64 = 16 * 1024;
+const DEFAULT_LIMIT: usize = 200;
+
+/// One line of `desktop.log` split into the parts `append_log` writes.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    /// Byte offset of the line within its file.
+    pub offset: u64,
+    pub timestamp: String,
+    pub level: String,
+    pub line: String,
+}
+
+/// What the viewer wants to see. Empty `levels` means every level.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LogFilter {
+    pub levels: Vec<String>,
+    pub pattern: Option<String>,
+    pub ignore_case: bool,
+}
+
+/// Entries in file order plus the cursors to continue from.
+#[derive(Debug, Clone, Serialize)]
+pub struct LogPage {
+    pub entries: Vec<LogEntry>,
+    /// Pass as `before` to fetch the next older page; 0 once the start of the
+    /// file has been reached.
+    pub start: u64,
+    /// Pass as `from` to tail the file from where this page ended.
+    pub end: u64,
+    /// The file was shorter than the requested offset, i.e. it was rotated
+    /// and the page restarts at the beginning of the new file.
+    pub rotated: bool,
+}
+
+struct Matcher {
+    levels: Vec<String>,
+    pattern: Option<Regex>,
+}
+
+impl Matcher {
+    fn new(filter: &LogFilter) -> Result<Self, String> {
+        let pattern = match filter.pattern.as_deref().filter(|pattern| !pattern.is_empty()) {
+            Some(pattern) => Some(
+                RegexBuilder::new(pattern)
+                    .case_insensitive(filter.ignore_case)
+                    .build()
+                    .map_err(|err| format!("Invalid search pattern: {err}"))?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            levels: filter.levels.iter().map(|level| level.to_ascii_uppercase()).collect(),
+            pattern,
+        })
+    }
+
+    fn matches(&self, entry: &LogEntry) -> bool {
+        let level_ok = self.levels.is_empty() || self.levels.contains(&entry.level);
+        level_ok
+            && self
+                .pattern
+                .as_ref()
+                .map_or(true, |pattern| pattern.is_match(&entry.line))
+    }
+}
+
+/// Splits `[timestamp] [LEVEL] text`, or reads a JSON record written in the
+/// structured format. Lines in any other shape are kept whole with an empty
+/// level.
+pub fn parse_line(offset: u64, raw: &str) -> LogEntry {
+    if raw.starts_with('{') {
+        if let Some(entry) = parse_record(offset, raw) {
+            return entry;
+        }
+    }
+    let bracketed = |text: &str| -> Option<(String, String)> {
+        let rest = text.strip_prefix('[')?;
+        let (inner, rest) = rest.split_once("] ")?;
+        Some((inner.to_string(), rest.to_string()))
+    };
+    match bracketed(raw).and_then(|(timestamp, rest)| {
+        bracketed(&rest).map(|(level, line)| (timestamp, level, line))
+    }) {
+        Some((timestamp, level, line)) => LogEntry {
+            offset,
+            timestamp,
+            level,
+            line,
+        },
+        None => LogEntry {
+            offset,
+            timestamp: String::new(),
+            level: String::new(),
+            line: raw.to_string(),
+        },
+    }
+}
+
+fn parse_record(offset: u64, raw: &str) -> Option<LogEntry> {
+    let record: Value = serde_json::from_str(raw).ok()?;
+    let field = |name: &str| record.get(name).and_then(Value::as_str).map(str::to_string);
+    let line = match (field("message"), record.get("payload")) {
+        (Some(message), _) => message,
+        (None, Some(payload)) => payload.to_string(),
+        (None, None) => return None,
+    };
+    Some(LogEntry {
+        offset,
+        timestamp: field("ts").unwrap_or_default(),
+        level: field("level").unwrap_or_default(),
+        line,
+    })
+}
+
+/// Random access to a log file. Rotated `.gz` copies are inflated up front;
+/// they are bounded by the rotation size.
+enum Source {
+    Plain(File, u64),
+    Inflated(Vec<u8>),
+}
+
+impl Source {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        if path.extension().is_some_and(|ext| ext == "gz") {
+            let mut bytes = Vec::new();
+            GzDecoder::new(file).read_to_end(&mut bytes)?;
+            return Ok(Self::Inflated(bytes));
+        }
+        let len = file.seek(SeekFrom::End(0))?;
+        Ok(Self::Plain(file, len))
+    }
+
+    fn len(&self) -> u64 {
+        match self {
+            Self::Plain(_, len) => *len,
+            Self::Inflated(bytes) => bytes.len() as u64,
+        }
+    }
+
+    fn read(&mut self, start: u64, end: u64) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Plain(file, _) => {
+                let mut buf = vec![0; (end - start) as usize];
+                file.seek(SeekFrom::Start(start))?;
+                file.read_exact(&mut buf)?;
+                Ok(buf)
+            }
+            Self::Inflated(bytes) => Ok(bytes[start as usize..end as usize].to_vec()),
+        }
+    }
+}
+
+/// Returns up to `limit` matching entries that end at or before `before`
+/// (default: the end of the file), walking backwards so the newest lines come
+/// first to hand.
+pub fn read_page(
+    path: &Path,
+    before: Option<u64>,
+    limit: Option<usize>,
+    filter: &LogFilter,
+) -> Result<LogPage, String> {
+    let matcher = Matcher::new(filter)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).max(1);
+    let mut source = match Source::open(path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(LogPage {
+                entries: Vec::new(),
+                start: 0,
+                end: 0,
+                rotated: false,
+            })
+        }
+        Err(err) => return Err(format!("Unable to read {}: {err}", path.display())),
+    };
+
+    let len = source.len();
+    let end = before.map_or(len, |before| before.min(len));
+    // A trailing line that is still being written is left for `tail`.
+    let end = line_start_before(&mut source, end)
+        .map_err(|err| format!("Unable to read {}: {err}", path.display()))?;
+    let mut cursor = end;
+    // Bytes of the line straddling the previous chunk boundary.
+    let mut carry = Vec::new();
+    let mut newest_first = Vec::new();
+    let mut scanned = 0;
+
+    'scan: while cursor > 0 && scanned < MAX_SCAN {
+        let start = cursor.saturating_sub(CHUNK);
+        let mut buf = source
+            .read(start, cursor)
+            .map_err(|err| format!("Unable to read {}: {err}", path.display()))?;
+        scanned += cursor - start;
+        buf.extend_from_slice(&carry);
+
+        // Unless this is the start of the file, the first line may be partial.
+        let complete_from = if start == 0 {
+            0
+        } else {
+            match buf.iter().position(|byte| *byte == b'\n') {
+                Some(newline) => newline + 1,
+                None => buf.len(),
+            }
+        };
+        let complete = &buf[complete_from..];
+        let mut line_end = complete.len();
+        for line_start in line_starts(complete).into_iter().rev() {
+            let offset = start + (complete_from + line_start) as u64;
+            let raw = String::from_utf8_lossy(&complete[line_start..line_end]);
+            let raw = raw.trim_end_matches(['\r', '\n']);
+            line_end = line_start;
+            if raw.is_empty() {
+                continue;
+            }
+            let entry = parse_line(offset, raw);
+            if matcher.matches(&entry) {
+                newest_first.push(entry);
+                if newest_first.len() == limit {
+                    cursor = offset;
+                    carry.clear();
+                    break 'scan;
+                }
+            }
+        }
+        carry = buf[..complete_from].to_vec();
+        cursor = start;
+    }
+
+    let mut start = cursor + carry.len() as u64;
+    if cursor > 0 && start == end {
+        // The whole scan fell inside one line. Show its beginning rather
+        // than stall on it.
+        start = line_start_before(&mut source, cursor)
+            .map_err(|err| format!("Unable to read {}: {err}", path.display()))?;
+        let entry = truncated_entry(&mut source, start, end)
+            .map_err(|err| format!("Unable to read {}: {err}", path.display()))?;
+        if matcher.matches(&entry) {
+            newest_first.push(entry);
+        }
+    }
+
+    newest_first.reverse();
+    Ok(LogPage {
+        entries: newest_first,
+        start,
+        end,
+        rotated: false,
+    })
+}
+
+/// Returns the matching entries appended since `from`. A trailing line that
+/// is still being written is left for the next call.
+pub fn tail(path: &Path, from: u64, filter: &LogFilter) -> Result<LogPage, String> {
+    let matcher = Matcher::new(filter)?;
+    let mut source = match Source::open(path) {
+        Ok(source) => source,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Ok(LogPage {
+                entries: Vec::new(),
+                start: 0,
+                end: 0,
+                rotated: from > 0,
+            })
+        }
+        Err(err) => return Err(format!("Unable to read {}: {err}", path.display())),
+    };
+
+    let len = source.len();
+    let rotated = from > len;
+    let start = if rotated { 0 } else { from };
+    let stop = len.min(start + MAX_SCAN);
+    let buf = source
+        .read(start, stop)
+        .map_err(|err| format!("Unable to read {}: {err}", path.display()))?;
+    let complete_to = buf
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    if complete_to == 0 && buf.len() as u64 == MAX_SCAN {
+        // One line longer than the scan limit: once it is complete, show its
+        // beginning and move past it.
+        let read = |err: io::Error| format!("Unable to read {}: {err}", path.display());
+        let Some(line_end) = line_end_after(&mut source, stop).map_err(read)? else {
+            return Ok(LogPage {
+                entries: Vec::new(),
+                start,
+                end: start,
+                rotated,
+            });
+        };
+        let entry = truncated_entry(&mut source, start, line_end).map_err(read)?;
+        return Ok(LogPage {
+            entries: matcher.matches(&entry).then_some(entry).into_iter().collect(),
+            start,
+            end: line_end,
+            rotated,
+        });
+    }
+
+    let complete = &buf[..complete_to];
+    let starts = line_starts(complete);
+    let entries = starts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line_start)| {
+            let line_end = starts.get(index + 1).copied().unwrap_or(complete.len());
+            let raw = String::from_utf8_lossy(&complete[*line_start..line_end]);
+            let raw = raw.trim_end_matches(['\r', '\n']);
+            (!raw.is_empty()).then(|| parse_line(start + *line_start as u64, raw))
+        })
+        .filter(|entry| matcher.matches(entry))
+        .collect();
+
+    Ok(LogPage {
+        entries,
+        start,
+        end: start + complete_to as u64,
+        rotated,
+    })
+}
+
+/// Offset just past the last newline before `pos`; 0 if there is none.
+fn line_start_before(source: &mut Source, pos: u64) -> io::Result<u64> {
+    let mut cursor = pos;
+    while cursor > 0 {
+        let start = cursor.saturating_sub(CHUNK);
+        let buf = source.read(start, cursor)?;
+        if let Some(newline) = buf.iter().rposition(|byte| *byte == b'\n') {
+            return Ok(start + newline as u64 + 1);
+        }
+        cursor = start;
+    }
+    Ok(0)
+}
+
+/// Offset just past the first newline at or after `pos`; `None` while the
+/// line is still being written.
+fn line_end_after(source: &mut Source, pos: u64) -> io::Result<Option<u64>> {
+    let len = source.len();
+    let mut cursor = pos;
+    while cursor < len {
+        let end = len.min(cursor + CHUNK);
+        let buf = source.read(cursor, end)?;
+        if let Some(newline) = buf.iter().position(|byte| *byte == b'\n') {
+            return Ok(Some(cursor + newline as u64 + 1));
+        }
+        cursor = end;
+    }
+    Ok(None)
+}
+
+/// The line between `start` and `end`, cut to its first `MAX_SHOWN` bytes.
+fn truncated_entry(source: &mut Source, start: u64, end: u64) -> io::Result<LogEntry> {
+    let head = source.read(start, end.min(start + MAX_SHOWN))?;
+    let raw = format!(
+        "{} … [truncated, {} bytes]",
+        String::from_utf8_lossy(&head).trim_end_matches(['\r', '\n']),
+        end - start
+    );
+    Ok(parse_line(start, &raw))
+}
+
+fn line_starts(bytes: &[u8]) -> Vec<usize> {
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+    std::iter::once(0)
+        .chain(
+            bytes
+                .iter()
+                .enumerate()
+                .filter(|(index, byte)| **byte == b'\n' && index + 1 < bytes.len())
+                .map(|(index, _)| index + 1),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write, path::PathBuf};
+
+    use super::*;
+
+    fn scratch(name: &str, contents: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("logview-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("desktop.log");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn lines(count: usize) -> String {
+        (0..count)
+            .map(|i| {
+                let level = if i % 3 == 0 { "ERROR" } else { "INFO" };
+                format!("[2024-01-02T03:04:05Z] [{level}] line {i}\n")
+            })
+            .collect()
+    }
+
+    fn texts(page: &LogPage) -> Vec<&str> {
+        page.entries.iter().map(|entry| entry.line.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_text_and_json_lines() {
+        let entry = parse_line(7, "[2024-01-02T03:04:05Z] [WARN] careful [x]");
+        assert_eq!(
+            (entry.offset, entry.timestamp.as_str(), entry.level.as_str(), entry.line.as_str()),
+            (7, "2024-01-02T03:04:05Z", "WARN", "careful [x]")
+        );
+        let entry = parse_line(0, r#"{"ts":"t","level":"STDOUT","event":"status","payload":{"a":1}}"#);
+        assert_eq!((entry.level.as_str(), entry.line.as_str()), ("STDOUT", r#"{"a":1}"#));
+        let entry = parse_line(0, "no brackets here");
+        assert_eq!((entry.level.as_str(), entry.line.as_str()), ("", "no brackets here"));
+    }
+
+    #[test]
+    fn pages_walk_backwards_to_the_start() {
+        let path = scratch("pages", &lines(5));
+        let filter = LogFilter::default();
+        let newest = read_page(&path, None, Some(2), &filter).unwrap();
+        assert_eq!(texts(&newest), ["line 3", "line 4"]);
+        let older = read_page(&path, Some(newest.start), Some(2), &filter).unwrap();
+        assert_eq!(texts(&older), ["line 1", "line 2"]);
+        let oldest = read_page(&path, Some(older.start), Some(2), &filter).unwrap();
+        assert_eq!(texts(&oldest), ["line 0"]);
+        assert_eq!(oldest.start, 0);
+    }
+
+    #[test]
+    fn pages_span_chunk_boundaries() {
+        let path = scratch("chunks", &lines(6_000));
+        let filter = LogFilter::default();
+        let mut seen = Vec::new();
+        let mut before = None;
+        loop {
+            let page = read_page(&path, before, Some(700), &filter).unwrap();
+            let mut batch: Vec<String> = page.entries.iter().map(|entry| entry.line.clone()).collect();
+            batch.append(&mut seen);
+            seen = batch;
+            if page.start == 0 {
+                break;
+            }
+            before = Some(page.start);
+        }
+        let expected: Vec<String> = (0..6_000).map(|i| format!("line {i}")).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn filters_by_level_and_pattern() {
+        let path = scratch("filter", &lines(10));
+        let filter = LogFilter {
+            levels: vec!["error".to_string()],
+            pattern: Some("LINE [69]".to_string()),
+            ignore_case: true,
+        };
+        assert_eq!(texts(&read_page(&path, None, None, &filter).unwrap()), ["line 6", "line 9"]);
+
+        let invalid = LogFilter {
+            pattern: Some("(".to_string()),
+            ..LogFilter::default()
+        };
+        let err = read_page(&path, None, None, &invalid).unwrap_err();
+        assert!(err.starts_with("Invalid search pattern"), "{err}");
+    }
+
+    #[test]
+    fn tail_leaves_a_partial_line_for_later() {
+        let path = scratch("tail", &lines(2));
+        let filter = LogFilter::default();
+        let page = read_page(&path, None, None, &filter).unwrap();
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "[t] [INFO] line 2\n[t] [INFO] li").unwrap();
+        let tailed = tail(&path, page.end, &filter).unwrap();
+        assert_eq!(texts(&tailed), ["line 2"]);
+        assert!(!tailed.rotated);
+
+        writeln!(file, "ne 3").unwrap();
+        let tailed = tail(&path, tailed.end, &filter).unwrap();
+        assert_eq!(texts(&tailed), ["line 3"]);
+        assert_eq!(tailed.end, fs::metadata(&path).unwrap().len());
+    }
+
+    #[test]
+    fn tail_restarts_after_rotation() {
+        let path = scratch("rotated", &lines(1));
+        let tailed = tail(&path, 10_000, &LogFilter::default()).unwrap();
+        assert!(tailed.rotated);
+        assert_eq!(texts(&tailed), ["line 0"]);
+    }
+
+    #[test]
+    fn missing_file_is_an_empty_page() {
+        let path = std::env::temp_dir().join("logview-missing/desktop.log");
+        let page = read_page(&path, None, None, &LogFilter::default()).unwrap();
+        assert!(page.entries.is_empty());
+        assert!(tail(&path, 5, &LogFilter::default()).unwrap().rotated);
+    }
+
+    #[test]
+    fn a_line_longer_than_the_scan_is_truncated_not_stalled() {
+        let huge = format!("[t] [ERROR] {}\n", "x".repeat(MAX_SCAN as usize + 10));
+        let contents = format!("[t] [INFO] before\n{huge}[t] [INFO] after\n");
+        let path = scratch("oversize", &contents);
+        let filter = LogFilter::default();
+        let is_truncated = |entry: &LogEntry| {
+            entry.level == "ERROR"
+                && entry.line.starts_with("xxx")
+                && entry.line.ends_with(&format!(" … [truncated, {} bytes]", huge.len()))
+                && entry.line.len() < MAX_SHOWN as usize + 100
+        };
+
+        let newest = read_page(&path, None, Some(10), &filter).unwrap();
+        assert_eq!(texts(&newest), ["after"]);
+        let middle = read_page(&path, Some(newest.start), Some(10), &filter).unwrap();
+        assert_eq!(middle.entries.len(), 1);
+        assert!(is_truncated(&middle.entries[0]));
+        assert_eq!(middle.start, "[t] [INFO] before\n".len() as u64);
+        let oldest = read_page(&path, Some(middle.start), Some(10), &filter).unwrap();
+        assert_eq!(texts(&oldest), ["before"]);
+        assert_eq!(oldest.start, 0);
+
+        let first = tail(&path, 0, &filter).unwrap();
+        assert_eq!(texts(&first), ["before"]);
+        let second = tail(&path, first.end, &filter).unwrap();
+        assert_eq!(second.entries.len(), 1);
+        assert!(is_truncated(&second.entries[0]));
+        let third = tail(&path, second.end, &filter).unwrap();
+        assert_eq!(texts(&third), ["after"]);
+        assert_eq!(third.end, contents.len() as u64);
+    }
+
+    #[test]
+    fn tail_waits_for_an_oversize_line_to_end() {
+        let partial = format!("[t] [INFO] {}", "x".repeat(MAX_SCAN as usize + 10));
+        let path = scratch("oversize-partial", &partial);
+        let tailed = tail(&path, 0, &LogFilter::default()).unwrap();
+        assert!(tailed.entries.is_empty());
+        assert_eq!(tailed.end, 0);
+        assert_eq!(read_page(&path, None, None, &LogFilter::default()).unwrap().end, 0);
+    }
+}
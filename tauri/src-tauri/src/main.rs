@@ -1,38 +1,115 @@
 #![cfg_attr(all(not(debug_assertions), target_os = "windows"), windows_subsystem = "windows")]
 
+mod claude;
+mod control;
+mod diagnostics;
+mod health;
+mod icon;
+mod instance;
+mod logging;
+mod logview;
+mod notify;
+mod patterns;
+mod pidfile;
+mod port;
+mod protocol;
+mod redact;
+mod settings;
+mod snapshot;
+mod stderr;
+mod supervisor;
+mod tray;
+
 use std::{
+    collections::{BTreeMap, HashSet},
     env, fs,
     path::{Path, PathBuf},
-    process::Stdio,
-    sync::{Arc, Mutex as StdMutex},
-    time::Duration,
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex as StdMutex,
+    },
+    time::{Duration, Instant},
 };
 
 use chrono::Utc;
 use dirs::home_dir;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use tauri::{
-    AppHandle, Manager, State,
-    menu::{Menu, MenuItem},
+    AppHandle, Emitter, Manager, RunEvent, State,
     tray::{TrayIcon, TrayIconBuilder},
 };
 use tauri::async_runtime::Mutex;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_shell::ShellExt;
 use tokio::{
-    fs::OpenOptions,
-    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
     process::{Child, Command},
-    time::sleep,
+    sync::watch,
+    time::{sleep, timeout},
 };
 
+use claude::{ClaudeConfigStatus, ConfigBackup, DiffKind};
+use control::ControlChannel;
+use diagnostics::Bundle;
+use health::{HealthCounts, Readiness};
+use icon::{IconSpec, TrayVisual};
+use instance::Claim;
+use logging::{LogFailures, LogFile, LogWriter};
+use logview::{LogFilter, LogPage};
+use notify::{Notification, Notifications, Notifier};
+use pidfile::{DaemonRecord, PidFile};
+use port::PortConflict;
+use protocol::{Handshake, ProtocolState};
+use redact::Redactor;
+use settings::{DesktopSettings, LogSettings, RedactSettings};
+use snapshot::DaemonSnapshot;
+use stderr::{DaemonWarning, Severity, StderrClassifier, Warnings};
+use supervisor::{describe_exit, RestartDecision, RestartPolicy, StopOutcome, StopReport};
+use tray::{MenuModel, TrayInput};
+
+/// Carries a fresh `UiStatus` whenever the shell's view of the proxy changes.
+const STATUS_EVENT: &str = "proxy://status";
+/// Carries every line appended to `desktop.log`.
+const LOG_EVENT: &str = "proxy://log";
+/// Carries errors reported by the daemon or the supervisor.
+const ERROR_EVENT: &str = "proxy://error";
+/// Carries the arguments of a second launch that was folded into this one.
+const INSTANCE_EVENT: &str = "app://second-instance";
+/// How often the tray tooltip refreshes while a rate-limit countdown runs.
+const TRAY_TICK: Duration = Duration::from_secs(1);
+
 #[derive(Clone)]
 struct ProxyState {
     repo_root: Arc<PathBuf>,
-    log_path: Arc<PathBuf>,
+    /// Which `detect_repo_root` probe found `repo_root`.
+    repo_probe: &'static str,
+    log: Arc<LogWriter>,
+    redactor: Arc<Redactor>,
+    stderr: Arc<StderrClassifier>,
+    settings: Arc<DesktopSettings>,
     child: Arc<Mutex<Option<Child>>>,
+    /// `None` if another shell holds the daemon lock.
+    pidfile: Arc<Option<PidFile>>,
+    control: Arc<ControlChannel>,
+    /// Last `phase` reported by the daemon's `status` events.
+    phase: Arc<watch::Sender<Option<String>>>,
     status: Arc<Mutex<AppStatus>>,
+    restart_policy: Arc<Mutex<RestartPolicy>>,
     tray: Arc<StdMutex<Option<TrayIcon>>>,
+    /// What the tray menu currently shows.
+    menu_model: Arc<StdMutex<Option<MenuModel>>>,
+    /// What the tray icon currently shows.
+    icon_spec: Arc<StdMutex<Option<IconSpec>>>,
+    /// Set while the countdown ticker runs.
+    tray_ticking: Arc<AtomicBool>,
+    app: Arc<StdMutex<Option<AppHandle>>>,
+    notifications: Arc<StdMutex<Notifications>>,
+    /// Schema warnings already written to the log, so heartbeats don't repeat them.
+    schema_warnings: Arc<StdMutex<HashSet<String>>>,
 }
 
 #[derive(Clone, Default)]
@@ -40,28 +117,53 @@ struct AppStatus {
     running: bool,
     last_error: Option<String>,
     last_update: Option<String>,
-    snapshot: Option<Value>,
+    snapshot: Option<DaemonSnapshot>,
+    /// Automatic restarts performed since the user last started the proxy.
+    restart_count: u32,
+    last_exit_code: Option<i32>,
+    /// Set when the supervisor gave up after too many exits in a short window.
+    crash_loop: bool,
+    /// A restart is scheduled; cleared by `stop_proxy_impl` to cancel it.
+    restart_pending: bool,
+    last_stop: Option<StopReport>,
+    /// Result of the last Claude settings check, reused when pushing status.
+    config: Option<ClaudeConfigStatus>,
+    /// Incremented on every spawn so timers can tell which daemon they belong to.
+    generation: u64,
+    protocol: ProtocolState,
+    handshake: Option<Handshake>,
+    /// Events with an `event` type the shell does not understand, by type.
+    unknown_events: BTreeMap<String, u64>,
+    readiness: Readiness,
+    health: Option<HealthCounts>,
+    /// Port the current (or last) daemon was started on.
+    port: Option<u16>,
+    /// Set when the preferred port was taken and no alternative was used.
+    port_conflict: Option<PortConflict>,
+    /// Daemon left by a previous session that this shell monitors but did not spawn.
+    adopted_pid: Option<u32>,
+    /// Stderr reports that were not failures, newest first.
+    warnings: Warnings,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct ProxyEvent {
     event: String,
     phase: Option<String>,
     snapshot: Option<Value>,
     message: Option<String>,
     reason: Option<String>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct ClaudeConfigStatus {
-    healthy: bool,
-    port: i64,
-    settings_path: String,
-    expected: Value,
-    current: Value,
-    #[serde(default)]
-    env: Option<Value>,
+    // `hello` fields
+    protocol: Option<u32>,
+    capabilities: Option<Vec<String>>,
+    version: Option<String>,
+    node: Option<String>,
+    pid: Option<u32>,
+    // `response` fields
+    id: Option<u64>,
+    ok: Option<bool>,
+    result: Option<Value>,
+    error: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Default)]
@@ -69,31 +171,101 @@ struct UiStatus {
     running: bool,
     last_error: Option<String>,
     last_update: Option<String>,
-    snapshot: Option<Value>,
+    snapshot: Option<DaemonSnapshot>,
     log_path: String,
+    /// Errors writing `desktop.log` itself.
+    log_failures: LogFailures,
     config: Option<ClaudeConfigStatus>,
+    restart_count: u32,
+    last_exit_code: Option<i32>,
+    crash_loop: bool,
+    last_stop: Option<StopReport>,
+    protocol: ProtocolState,
+    handshake: Option<Handshake>,
+    unknown_events: BTreeMap<String, u64>,
+    readiness: Readiness,
+    health: Option<HealthCounts>,
+    port: u16,
+    port_conflict: Option<PortConflict>,
+    adopted_pid: Option<u32>,
+    warnings: Vec<DaemonWarning>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LogLine {
+    timestamp: String,
+    level: String,
+    line: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ErrorNotice {
+    timestamp: String,
+    message: String,
 }
 
 impl ProxyState {
     fn new() -> Self {
-        let repo_root = detect_repo_root();
+        let (repo_root, repo_probe) = detect_repo_root();
 
-        let log_path = home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".antigravity-proxy")
-            .join("desktop.log");
+        let (settings, settings_error) = DesktopSettings::load(&settings::settings_path());
+        let log = Arc::new(LogWriter::spawn(
+            settings::state_dir().join("desktop.log"),
+            settings.log.clone(),
+        ));
+        let (redactor, redact_errors) = Redactor::new(&settings.redact);
+        let (stderr, stderr_errors) = StderrClassifier::new(&settings.stderr);
+        let restart_policy = RestartPolicy::new(settings.restart.clone());
+        let (phase, _) = watch::channel(None);
+        let (pidfile, pidfile_error) = match PidFile::acquire(&settings::state_dir()) {
+            Ok(pidfile) => (Some(pidfile), None),
+            Err(err) => (None, Some(err)),
+        };
 
-        if let Some(parent) = log_path.parent() {
-            let _ = fs::create_dir_all(parent);
-        }
+        let app = Arc::new(StdMutex::new(None));
+        let notifications = Notifications::new(
+            Box::new(SystemNotifier {
+                app: app.clone(),
+                log: log.clone(),
+            }),
+            settings.notify.clone(),
+        );
 
-        Self {
+        let state = Self {
             repo_root: Arc::new(repo_root),
-            log_path: Arc::new(log_path),
+            repo_probe,
+            log,
+            redactor: Arc::new(redactor),
+            stderr: Arc::new(stderr),
+            settings: Arc::new(settings),
             child: Arc::new(Mutex::new(None)),
+            pidfile: Arc::new(pidfile),
+            control: Arc::new(ControlChannel::default()),
+            phase: Arc::new(phase),
             status: Arc::new(Mutex::new(AppStatus::default())),
+            restart_policy: Arc::new(Mutex::new(restart_policy)),
             tray: Arc::new(StdMutex::new(None)),
+            menu_model: Arc::new(StdMutex::new(None)),
+            icon_spec: Arc::new(StdMutex::new(None)),
+            tray_ticking: Arc::new(AtomicBool::new(false)),
+            app,
+            notifications: Arc::new(StdMutex::new(notifications)),
+            schema_warnings: Arc::new(StdMutex::new(HashSet::new())),
+        };
+
+        let startup_errors = [settings_error, pidfile_error]
+            .into_iter()
+            .flatten()
+            .chain(redact_errors)
+            .chain(stderr_errors);
+        for message in startup_errors {
+            let state_clone = state.clone();
+            tauri::async_runtime::spawn(async move {
+                state_clone.append_log("ERROR", &message).await;
+            });
         }
+
+        state
     }
 
     fn repo_root(&self) -> &Path {
@@ -101,7 +273,7 @@ impl ProxyState {
     }
 
     fn log_path(&self) -> &Path {
-        &self.log_path
+        self.log.path()
     }
 
     fn attach_tray(&self, tray: TrayIcon) {
@@ -110,39 +282,288 @@ impl ProxyState {
         }
     }
 
-    async fn append_log(&self, level: &str, line: &str) {
-        if let Ok(mut file) = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(self.log_path())
+    fn invalidate_menu(&self) {
+        if let Ok(mut shown) = self.menu_model.lock() {
+            *shown = None;
+        }
+    }
+
+    fn attach_app(&self, app: AppHandle) {
+        if let Ok(mut guard) = self.app.lock() {
+            *guard = Some(app);
+        }
+    }
+
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Ok(guard) = self.app.lock() {
+            if let Some(app) = guard.as_ref() {
+                let _ = app.emit(event, payload);
+            }
+        }
+    }
+
+    /// Refreshes the tray and pushes the current status to every window.
+    async fn publish(&self) {
+        let _ = self.update_tray().await;
+        if self.has_pending_wait().await {
+            self.ensure_tray_ticker();
+        }
+        let ui = self.current_status().await;
+        self.emit(STATUS_EVENT, ui);
+    }
+
+    async fn has_pending_wait(&self) -> bool {
+        let now_ms = Utc::now().timestamp_millis();
+        self.status
+            .lock()
             .await
-        {
-            let timestamp = Utc::now().to_rfc3339();
-            let entry = format!("[{timestamp}] [{level}] {line}\n");
-            let _ = file.write_all(entry.as_bytes()).await;
+            .snapshot
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.shortest_wait_ms(now_ms).is_some())
+    }
+
+    /// Re-renders the tray every second while an account is cooling down so
+    /// the tooltip countdowns move. Exits once nothing is pending; the next
+    /// `publish` that sees a wait starts it again.
+    fn ensure_tray_ticker(&self) {
+        if self.tray_ticking.swap(true, Ordering::SeqCst) {
+            return;
         }
+        let state = self.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                sleep(TRAY_TICK).await;
+                let _ = state.update_tray().await;
+                if state.has_pending_wait().await {
+                    continue;
+                }
+                state.tray_ticking.store(false, Ordering::SeqCst);
+                // A snapshot may have brought a new wait in since the check.
+                if !state.has_pending_wait().await || state.tray_ticking.swap(true, Ordering::SeqCst) {
+                    break;
+                }
+            }
+        });
+    }
+
+    fn notify(&self, apply: impl FnOnce(&mut Notifications)) {
+        if let Ok(mut notifications) = self.notifications.lock() {
+            apply(&mut notifications);
+        }
+    }
+
+    fn emit_error(&self, message: &str) {
+        self.emit(
+            ERROR_EVENT,
+            ErrorNotice {
+                timestamp: now_string(),
+                message: message.to_string(),
+            },
+        );
+    }
+
+    /// Every line is passed through the redactor before it is persisted or
+    /// forwarded to the webview.
+    async fn append_log(&self, level: &str, line: &str) {
+        let line = self.redactor.redact(line);
+        self.log.append(level, &line);
+        self.emit(
+            LOG_EVENT,
+            LogLine {
+                timestamp: now_string(),
+                level: level.to_string(),
+                line: line.to_string(),
+            },
+        );
+    }
+
+    /// Parses a daemon snapshot and logs each schema warning the first time it is seen.
+    async fn parse_snapshot(&self, value: &Value) -> DaemonSnapshot {
+        let (snapshot, warnings) = DaemonSnapshot::from_value(value);
+        let fresh: Vec<String> = match self.schema_warnings.lock() {
+            Ok(mut seen) => warnings
+                .into_iter()
+                .filter(|warning| seen.insert(warning.clone()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        for warning in fresh {
+            self.append_log("WARN", &format!("Snapshot schema mismatch: {warning}"))
+                .await;
+        }
+        snapshot
     }
 
     async fn apply_event(&self, event: ProxyEvent) {
+        if event.event == "response" {
+            self.resolve_response(event).await;
+            return;
+        }
+
+        let snapshot = match event.snapshot.as_ref() {
+            Some(value) => Some(self.parse_snapshot(value).await),
+            None => None,
+        };
+        if let Some(snapshot) = &snapshot {
+            self.notify(|notifications| notifications.observe_snapshot(snapshot));
+        }
+
+        let mut verdict = None;
+        let mut first_unknown = None;
         {
             let mut status = self.status.lock().await;
             match event.event.as_str() {
+                "hello" => {
+                    let handshake = Handshake {
+                        protocol: event.protocol.unwrap_or_default(),
+                        capabilities: event.capabilities.unwrap_or_default(),
+                        backend_version: event.version,
+                        node_version: event.node,
+                        pid: event.pid,
+                    };
+                    let evaluated = protocol::evaluate(&handshake);
+                    status.protocol = evaluated.clone();
+                    status.handshake = Some(handshake);
+                    verdict = Some(evaluated);
+                }
                 "status" => {
-                    if let Some(snapshot) = event.snapshot {
+                    if let Some(snapshot) = snapshot {
                         status.snapshot = Some(snapshot);
                     }
                     status.running = event.phase.as_deref() != Some("stopped");
                     status.last_error = None;
                     status.last_update = Some(now_string());
+                    self.phase.send_replace(event.phase);
                 }
                 "error" => {
                     status.last_error = event.message.or(event.reason);
                     status.last_update = Some(now_string());
+                    if let Some(message) = status.last_error.as_deref() {
+                        self.emit_error(message);
+                    }
+                }
+                other => {
+                    let count = status.unknown_events.entry(other.to_string()).or_insert(0);
+                    *count += 1;
+                    if *count == 1 {
+                        first_unknown = Some(other.to_string());
+                    }
                 }
-                _ => {}
             }
         }
-        let _ = self.update_tray().await;
+
+        if let Some(kind) = first_unknown {
+            self.append_log("WARN", &format!("Ignoring unknown daemon event type '{kind}'"))
+                .await;
+        }
+        match verdict {
+            Some(ProtocolState::Incompatible(reason)) => self.refuse_daemon(reason).await,
+            Some(ProtocolState::Degraded(reason)) => self.append_log("WARN", &reason).await,
+            _ => {}
+        }
+        self.publish().await;
+    }
+
+    /// Only genuine failures become `last_error`; everything else is kept as
+    /// a warning.
+    async fn report_stderr(&self, lines: Vec<String>) {
+        let Some(report) = self.stderr.classify(&lines) else {
+            return;
+        };
+        match report.severity {
+            Severity::Error => {
+                self.apply_event(ProxyEvent {
+                    event: "error".to_string(),
+                    message: Some(report.message),
+                    ..ProxyEvent::default()
+                })
+                .await;
+            }
+            Severity::Warning | Severity::Noise => {
+                self.status.lock().await.warnings.record(
+                    report.message,
+                    now_string(),
+                    self.settings.stderr.max_warnings,
+                );
+                self.publish().await;
+            }
+        }
+    }
+
+    async fn resolve_response(&self, event: ProxyEvent) {
+        let Some(id) = event.id else {
+            let error = event.error.unwrap_or_default();
+            self.append_log("WARN", &format!("Daemon rejected a command: {error}"))
+                .await;
+            return;
+        };
+        let reply = if event.ok.unwrap_or(false) {
+            Ok(event.result.unwrap_or(Value::Null))
+        } else {
+            Err(event.error.unwrap_or_else(|| "Command failed".to_string()))
+        };
+        if !self.control.resolve(id, reply) {
+            self.append_log("WARN", &format!("Dropping late response to request {id}"))
+                .await;
+        }
+    }
+
+    /// Sends a command over the daemon's stdin and waits for its response.
+    async fn send_command(&self, command: &str, args: Value) -> Result<Value, String> {
+        let supported = self
+            .status
+            .lock()
+            .await
+            .handshake
+            .as_ref()
+            .is_some_and(|handshake| handshake.supports("control"));
+        if !supported {
+            return Err("The running backend does not accept control commands".to_string());
+        }
+        let deadline = Duration::from_millis(self.settings.control.timeout_ms);
+        self.control.request(command, args, deadline).await
+    }
+
+    async fn switch_account(&self, email: &str) -> Result<DaemonSnapshot, String> {
+        self.send_command("switch-account", json!({ "email": email }))
+            .await?;
+        self.append_log("INFO", &format!("Switched active account to {email}"))
+            .await;
+        self.request_status().await
+    }
+
+    async fn reload_proxy_config(&self) -> Result<Value, String> {
+        self.send_command("reload-config", json!({})).await
+    }
+
+    async fn flush_flows(&self) -> Result<u64, String> {
+        let result = self.send_command("flush-flows", json!({})).await?;
+        Ok(result.get("flushed").and_then(Value::as_u64).unwrap_or(0))
+    }
+
+    /// Asks the daemon for a fresh snapshot instead of waiting for the next heartbeat.
+    async fn request_status(&self) -> Result<DaemonSnapshot, String> {
+        let value = self.send_command("status", json!({})).await?;
+        let snapshot = self.parse_snapshot(&value).await;
+        {
+            let mut status = self.status.lock().await;
+            status.snapshot = Some(snapshot.clone());
+            status.last_update = Some(now_string());
+        }
+        self.publish().await;
+        Ok(snapshot)
+    }
+
+    /// Shuts down a daemon that failed the handshake. Runs in its own task because
+    /// the stop sequence waits on events delivered by the caller's line reader.
+    async fn refuse_daemon(&self, reason: String) {
+        self.append_log("ERROR", &format!("Refusing incompatible backend: {reason}"))
+            .await;
+        let state = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let _ = stop_proxy_impl(&state).await;
+            state.mark_stopped(Some(&reason)).await;
+        });
     }
 
     async fn current_status(&self) -> UiStatus {
@@ -153,55 +574,432 @@ impl ProxyState {
             last_update: status.last_update.clone(),
             snapshot: status.snapshot.clone(),
             log_path: self.log_path().display().to_string(),
-            config: None,
+            log_failures: self.log.failures(),
+            restart_count: status.restart_count,
+            last_exit_code: status.last_exit_code,
+            crash_loop: status.crash_loop,
+            last_stop: status.last_stop.clone(),
+            config: status.config.clone(),
+            protocol: status.protocol.clone(),
+            handshake: status.handshake.clone(),
+            unknown_events: status.unknown_events.clone(),
+            readiness: status.readiness.clone(),
+            health: status.health.clone(),
+            port: self.port_for(&status),
+            port_conflict: status.port_conflict.clone(),
+            adopted_pid: status.adopted_pid,
+            warnings: status.warnings.to_vec(),
         }
     }
 
-    async fn claude_config_status(&self) -> Option<ClaudeConfigStatus> {
-        match self.run_node_script("desktop/claude-config-status.js").await {
-            Ok(output) if !output.trim().is_empty() => {
-                serde_json::from_str::<ClaudeConfigStatus>(output.trim()).ok()
-            }
-            _ => None,
+    /// Port the proxy is (or will be) reachable on: what the daemon reports,
+    /// else what it was started with, else the configured preference.
+    fn port_for(&self, status: &AppStatus) -> u16 {
+        status
+            .snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.port)
+            .or(status.port)
+            .unwrap_or(self.settings.port.preferred)
+    }
+
+    async fn active_port(&self) -> u16 {
+        let status = self.status.lock().await;
+        self.port_for(&status)
+    }
+
+    /// Checks that `wanted` is free before spawning. If it is taken, either
+    /// moves to the next free port (when `autoSelect` is on) or records the
+    /// conflict, including who holds the port, and fails.
+    async fn choose_port(&self, wanted: u16) -> Result<u16, String> {
+        if port::is_port_free(wanted) {
+            self.status.lock().await.port_conflict = None;
+            return Ok(wanted);
         }
+
+        let conflict = PortConflict {
+            port: wanted,
+            owner: port::port_owner(wanted),
+            suggested: port::find_free_port(wanted, self.settings.port.search_range),
+        };
+        let description = conflict.describe();
+        if let (true, Some(free)) = (self.settings.port.auto_select, conflict.suggested) {
+            self.append_log("WARN", &format!("{description}; using {free} instead"))
+                .await;
+            self.status.lock().await.port_conflict = None;
+            return Ok(free);
+        }
+
+        self.append_log("ERROR", &description).await;
+        self.status.lock().await.port_conflict = Some(conflict);
+        self.publish().await;
+        Err(description)
     }
 
-    async fn repair_claude_config(&self) -> Result<ClaudeConfigStatus, String> {
-        let output = self
-            .run_node_script("desktop/claude-config-fix.js")
-            .await?;
-        serde_json::from_str::<ClaudeConfigStatus>(output.trim())
-            .map_err(|err| err.to_string())
+    /// Re-runs the Claude settings check and caches the result for later pushes.
+    async fn refresh_config(&self) -> ClaudeConfigStatus {
+        let config = claude::check(&claude::settings_path(), self.active_port().await);
+        self.notify(|notifications| {
+            notifications.observe_config(config.healthy, &config.settings_path)
+        });
+        self.status.lock().await.config = Some(config.clone());
+        config
+    }
+
+    /// Applies every expected key, or only `keys`.
+    async fn repair_claude_config(
+        &self,
+        keys: Option<&[String]>,
+    ) -> Result<ClaudeConfigStatus, String> {
+        let port = self.active_port().await;
+        let status = claude::repair(&claude::settings_path(), &claude::backup_dir(), port, keys)?;
+        self.append_log("INFO", &format!("Updated {}", status.settings_path))
+            .await;
+        Ok(status)
     }
 
-    async fn run_node_script(&self, relative: &str) -> Result<String, String> {
-        let node_bin = resolve_node_binary()?;
-        let script_path = self.repo_root().join(relative);
-        if !script_path.exists() {
-            return Err(format!("Script not found: {}", script_path.display()));
+    async fn detach_claude_config(&self) -> Result<ClaudeConfigStatus, String> {
+        let port = self.active_port().await;
+        let status = claude::detach(&claude::settings_path(), &claude::backup_dir(), port)?;
+        self.notify(Notifications::reset_config);
+        self.append_log("INFO", &format!("Removed the proxy env from {}", status.settings_path))
+            .await;
+        Ok(status)
+    }
+
+    /// Runs on the quit path, so it blocks and only logs.
+    fn detach_on_quit(&self) {
+        let backups = claude::backup_dir();
+        let keys = claude::attached_keys(&backups);
+        if !self.settings.claude.detach_on_quit || keys.is_empty() {
+            return;
         }
+        let port = self.settings.port.preferred;
+        let detached = claude::detach(&claude::settings_path(), &backups, port)
+            .and_then(|_| claude::request_reattach(&backups, keys));
+        match detached {
+            Ok(()) => self.log.append("INFO", "Detached Claude settings from the proxy on quit"),
+            Err(err) => self.log.append("ERROR", &format!("Unable to detach Claude settings: {err}")),
+        }
+    }
 
-        let output = Command::new(node_bin)
-            .arg(script_path)
-            .current_dir(self.repo_root())
-            .output()
-            .await
-            .map_err(|err| err.to_string())?;
+    /// The `attachOnStart` setting, run after a start the user asked for so
+    /// supervisor restarts never touch `settings.json`. If the user applied
+    /// only some keys, only those are put back.
+    async fn attach_on_start(&self) {
+        if !self.settings.claude.attach_on_start {
+            return;
+        }
+        let attached = claude::attached_keys(&claude::backup_dir());
+        let keys = (!attached.is_empty()).then_some(attached);
+        let config = claude::check(&claude::settings_path(), self.active_port().await);
+        let pending = config.diff.iter().any(|entry| {
+            entry.kind != DiffKind::Unchanged
+                && keys.as_ref().map_or(true, |keys| keys.contains(&entry.key))
+        });
+        if !pending {
+            return;
+        }
+        if let Err(err) = self.repair_claude_config(keys.as_deref()).await {
+            self.append_log("ERROR", &format!("Unable to attach Claude settings: {err}"))
+                .await;
+        }
+    }
 
-        if output.status.success() {
-            Ok(String::from_utf8_lossy(&output.stdout).to_string())
-        } else {
-            Err(String::from_utf8_lossy(&output.stderr).to_string())
+    async fn reattach_on_start(&self) {
+        if !self.settings.claude.detach_on_quit {
+            return;
+        }
+        let Some(keys) = claude::reattach_requested(&claude::backup_dir()) else {
+            return;
+        };
+        if let Err(err) = self.repair_claude_config(Some(&keys)).await {
+            self.append_log("ERROR", &format!("Unable to re-attach Claude settings: {err}"))
+                .await;
         }
     }
 
+    async fn restore_claude_config(&self, name: &str) -> Result<ClaudeConfigStatus, String> {
+        let port = self.active_port().await;
+        let status = claude::restore(&claude::settings_path(), &claude::backup_dir(), name, port)?;
+        self.notify(Notifications::reset_config);
+        self.append_log("INFO", &format!("Restored {} from {name}", status.settings_path))
+            .await;
+        Ok(status)
+    }
+
+    fn record_daemon(&self, pid: u32, port: u16) {
+        let Some(pidfile) = self.pidfile.as_ref() else {
+            return;
+        };
+        let record = DaemonRecord {
+            pid,
+            port,
+            started_at: now_string(),
+            shell_pid: std::process::id(),
+        };
+        if let Err(err) = pidfile.write(&record) {
+            let state = self.clone();
+            tauri::async_runtime::spawn(async move {
+                state.append_log("WARN", &err).await;
+            });
+        }
+    }
+
+    fn forget_daemon(&self) {
+        if let Some(pidfile) = self.pidfile.as_ref() {
+            pidfile.clear();
+        }
+    }
+
+    /// Looks for a daemon left running by a previous session that exited
+    /// without stopping it, and adopts or terminates it per `orphanPolicy`.
+    /// Unix only; see `PidFile::acquire`.
+    #[cfg(not(windows))]
+    async fn recover_orphan(&self) {
+        use pidfile::Liveness;
+        use settings::OrphanPolicy;
+
+        let Some(pidfile) = self.pidfile.as_ref() else {
+            return;
+        };
+        let record = match pidfile.read() {
+            Ok(Some(record)) => record,
+            Ok(None) => return,
+            Err(err) => {
+                self.append_log("WARN", &err).await;
+                pidfile.clear();
+                return;
+            }
+        };
+        let stale = match pidfile::classify(record.pid) {
+            Liveness::Gone => Some("is gone"),
+            Liveness::Reused => Some("is alive but is not our daemon"),
+            Liveness::Ours => None,
+        };
+        if let Some(reason) = stale {
+            self.append_log(
+                "INFO",
+                &format!("Removing stale {} (pid {} {reason})", pidfile.path().display(), record.pid),
+            )
+            .await;
+            pidfile.clear();
+            return;
+        }
+
+        match self.settings.startup.orphan_policy {
+            OrphanPolicy::Terminate => {
+                self.append_log(
+                    "WARN",
+                    &format!("Terminating proxy (pid {}) left by a previous session", record.pid),
+                )
+                .await;
+                let report = terminate_pid(self, record.pid).await;
+                let level = if report.outcome == StopOutcome::Unresponsive {
+                    "ERROR"
+                } else {
+                    "INFO"
+                };
+                self.append_log(level, &report.describe()).await;
+                pidfile.clear();
+            }
+            OrphanPolicy::Adopt => self.adopt(record).await,
+        }
+    }
+
+    /// Takes over monitoring of an orphaned daemon. Its stdio went away with the
+    /// previous shell, so only `/health` and the pid are available.
+    #[cfg(not(windows))]
+    async fn adopt(&self, record: DaemonRecord) {
+        self.append_log(
+            "INFO",
+            &format!(
+                "Re-attached to proxy (pid {}) on port {} left by a previous session",
+                record.pid, record.port
+            ),
+        )
+        .await;
+        {
+            let mut status = self.status.lock().await;
+            status.generation += 1;
+            status.running = true;
+            status.adopted_pid = Some(record.pid);
+            status.port = Some(record.port);
+            status.readiness = Readiness::Starting;
+            status.protocol = ProtocolState::Degraded(
+                "Re-attached to a daemon from a previous session; live events and control commands are unavailable"
+                    .to_string(),
+            );
+            status.handshake = None;
+            status.last_update = Some(now_string());
+        }
+        self.publish().await;
+        spawn_adopted_monitor(self.clone(), record.pid);
+    }
+
     async fn mark_stopped(&self, message: Option<&str>) {
         let mut status = self.status.lock().await;
         status.running = false;
+        status.readiness = Readiness::Stopped;
+        status.health = None;
         status.last_error = message.map(|m| m.to_string());
         status.last_update = Some(now_string());
         drop(status);
-        let _ = self.update_tray().await;
+        if let Some(message) = message {
+            self.emit_error(message);
+        }
+        self.publish().await;
+    }
+
+    /// Called by the watchdog when the daemon exited without `stop_proxy_impl`
+    /// asking it to. Applies the restart policy and schedules a respawn.
+    async fn handle_unexpected_exit(&self, exit: ExitStatus) {
+        let description = describe_exit(&exit);
+        self.append_log("ERROR", &description).await;
+        self.notify(|notifications| notifications.daemon_exited(&description));
+        self.status.lock().await.last_exit_code = exit.code();
+
+        let decision = self.restart_policy.lock().await.record_exit(Instant::now());
+        match decision {
+            RestartDecision::Disabled => {
+                self.mark_stopped(Some(&description)).await;
+            }
+            RestartDecision::CrashLoop { exits, window } => {
+                let message = format!(
+                    "Proxy exited {exits} times within {}s; automatic restart disabled",
+                    window.as_secs()
+                );
+                self.append_log("ERROR", &message).await;
+                self.status.lock().await.crash_loop = true;
+                self.mark_stopped(Some(&message)).await;
+            }
+            RestartDecision::Restart { attempt, delay } => {
+                let message = format!(
+                    "{description}; restarting in {} (attempt {attempt})",
+                    format_duration(delay.as_millis() as u64)
+                );
+                self.append_log("ERROR", &message).await;
+                {
+                    let mut status = self.status.lock().await;
+                    status.running = false;
+                    status.restart_pending = true;
+                    status.last_error = Some(message);
+                    status.last_update = Some(now_string());
+                }
+                self.publish().await;
+
+                let state = self.clone();
+                tauri::async_runtime::spawn(async move {
+                    sleep(delay).await;
+                    state.restart_after_exit().await;
+                });
+            }
+        }
+    }
+
+    async fn restart_after_exit(&self) {
+        {
+            let mut status = self.status.lock().await;
+            if !status.restart_pending {
+                return;
+            }
+            status.restart_pending = false;
+        }
+
+        let spawned = match self.choose_port(self.active_port().await).await {
+            Ok(port) => spawn_daemon(self, port).await,
+            Err(err) => Err(err),
+        };
+        match spawned {
+            Ok(()) => {
+                {
+                    let mut status = self.status.lock().await;
+                    status.running = true;
+                    status.restart_count += 1;
+                    status.last_update = Some(now_string());
+                }
+                self.append_log("INFO", "Proxy restarted by supervisor").await;
+                self.publish().await;
+                if let Err(err) = self.settle_readiness().await {
+                    self.append_log("ERROR", &err).await;
+                }
+            }
+            Err(err) => {
+                self.append_log("ERROR", &format!("Restart failed: {err}")).await;
+                self.mark_stopped(Some(&err)).await;
+            }
+        }
+    }
+
+    /// Waits for a freshly spawned daemon to report `started` and answer
+    /// `/health`, then records whether it is ready or degraded. Fails only if
+    /// the daemon went away while starting.
+    async fn settle_readiness(&self) -> Result<(), String> {
+        let (readiness, counts) = self.await_readiness().await?;
+        if let Readiness::Degraded(reason) = &readiness {
+            self.append_log("WARN", &format!("Proxy degraded: {reason}"))
+                .await;
+        }
+        {
+            let mut status = self.status.lock().await;
+            if status.readiness == Readiness::Starting {
+                status.readiness = readiness;
+                status.health = counts;
+            }
+        }
+        self.publish().await;
+        Ok(())
+    }
+
+    async fn await_readiness(&self) -> Result<(Readiness, Option<HealthCounts>), String> {
+        let startup = &self.settings.startup;
+        let timeout_label = format_duration(startup.ready_timeout_ms);
+        let deadline = Instant::now() + Duration::from_millis(startup.ready_timeout_ms);
+        let poll = Duration::from_millis(startup.health_poll_interval_ms.max(50));
+        let mut phase = self.phase.subscribe();
+
+        loop {
+            if matches!(
+                phase.borrow_and_update().as_deref(),
+                Some("started" | "heartbeat")
+            ) {
+                break;
+            }
+            self.ensure_still_starting().await?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                let reason = format!("Proxy did not report started within {timeout_label}");
+                return Ok((Readiness::Degraded(reason), None));
+            }
+            let _ = timeout(poll.min(remaining), phase.changed()).await;
+        }
+
+        let port = self.active_port().await;
+
+        loop {
+            self.ensure_still_starting().await?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let attempt = remaining.min(Duration::from_secs(2));
+            match health::probe(port, attempt).await {
+                Ok(report) => return Ok((report.readiness(), Some(report.counts))),
+                Err(err) if Instant::now() >= deadline => {
+                    let reason = format!("/health did not answer within {timeout_label}: {err}");
+                    return Ok((Readiness::Degraded(reason), None));
+                }
+                Err(_) => sleep(poll).await,
+            }
+        }
+    }
+
+    async fn ensure_still_starting(&self) -> Result<(), String> {
+        let status = self.status.lock().await;
+        if status.readiness == Readiness::Starting {
+            return Ok(());
+        }
+        Err(status
+            .last_error
+            .clone()
+            .unwrap_or_else(|| "Proxy stopped while starting".to_string()))
     }
 
     async fn update_tray(&self) -> tauri::Result<()> {
@@ -209,16 +1007,7 @@ impl ProxyState {
         let has_rate_limit = status
             .snapshot
             .as_ref()
-            .and_then(|snapshot| snapshot.get("accounts"))
-            .and_then(|accounts| accounts.as_array())
-            .map(|accounts| {
-                accounts.iter().any(|acc| {
-                    acc.get("isRateLimited")
-                        .and_then(|v| v.as_bool())
-                        .unwrap_or(false)
-                })
-            })
-            .unwrap_or(false);
+            .is_some_and(DaemonSnapshot::has_rate_limited_account);
 
         let visual = if status.running {
             if has_rate_limit {
@@ -232,84 +1021,99 @@ impl ProxyState {
             TrayVisual::Stopped
         };
 
-        let tooltip = if status.running {
-            if let Some(snapshot) = status.snapshot.as_ref() {
-                let port = snapshot
-                    .get("port")
-                    .and_then(|p| p.as_i64())
-                    .unwrap_or(8080);
-                let account = snapshot
-                    .get("currentAccount")
-                    .and_then(|a| a.as_str())
-                    .unwrap_or("unknown");
-                format!("Proxy running on :{port} · {account}")
-            } else {
-                "Proxy running".to_string()
-            }
-        } else if let Some(err) = status.last_error.clone() {
-            err
-        } else if let Some(wait_ms) = status
-            .snapshot
-            .as_ref()
-            .and_then(shortest_wait_ms)
-            .filter(|ms| *ms > 0)
-        {
-            format!(
-                "Rate limited · next slot in {}",
-                format_duration(wait_ms as u64)
-            )
-        } else {
-            "Proxy stopped".to_string()
+        let input = TrayInput {
+            running: status.running,
+            starting: (status.running && status.readiness == Readiness::Starting)
+                || status.restart_pending,
+            port: self.port_for(&status),
+            snapshot: status.snapshot.as_ref(),
+            last_error: status.last_error.as_deref(),
+            config_healthy: status.config.as_ref().map(|config| config.healthy),
+            now_ms: Utc::now().timestamp_millis(),
+        };
+        let tooltip = tray::tooltip(&input);
+        let model = MenuModel::new(&input);
+        let app = self.app.lock().ok().and_then(|guard| guard.clone());
+        let spec = IconSpec {
+            visual,
+            badge: status
+                .running
+                .then(|| status.snapshot.as_ref().map(DaemonSnapshot::rate_limited_count))
+                .flatten(),
+            size: icon::size_for_scale(app.as_ref().map_or(1.0, tray_scale)),
+            template: app.as_ref().is_some_and(template_icons),
         };
 
         if let Ok(guard) = self.tray.lock() {
             if let Some(tray) = guard.as_ref() {
-                tray.set_icon(Some(visual.icon()))?;
+                if let Ok(mut shown) = self.icon_spec.lock() {
+                    if shown.as_ref() != Some(&spec) {
+                        tray.set_icon(Some(tray_image(&spec)))?;
+                        tray.set_icon_as_template(spec.template)?;
+                        *shown = Some(spec);
+                    }
+                }
                 tray.set_tooltip(Some(tooltip.as_str()))?;
+                if let (Some(app), Ok(mut shown)) = (app, self.menu_model.lock()) {
+                    if shown.as_ref() != Some(&model) {
+                        tray.set_menu(Some(model.build(&app)?))?;
+                        *shown = Some(model);
+                    }
+                }
             }
         }
         Ok(())
     }
 }
 
-fn shortest_wait_ms(snapshot: &Value) -> Option<i64> {
-    let accounts = snapshot.get("accounts")?.as_array()?;
-    let now = Utc::now().timestamp_millis();
-    accounts
-        .iter()
-        .filter_map(|acc| acc.get("nextAvailableAt").and_then(|v| v.as_i64()))
-        .map(|ts| ts - now)
-        .filter(|delta| *delta > 0)
-        .min()
-}
-
-enum TrayVisual {
-    Running,
-    Warning,
-    Stopped,
+/// Shows notifications through the OS once the app is up; earlier ones are
+/// dropped. Failures go to `desktop.log`.
+struct SystemNotifier {
+    app: Arc<StdMutex<Option<AppHandle>>>,
+    log: Arc<LogWriter>,
 }
 
-impl TrayVisual {
-    fn icon(&self) -> tauri::image::Image<'static> {
-        let color = match self {
-            TrayVisual::Running => [16, 185, 129],
-            TrayVisual::Warning => [251, 191, 36],
-            TrayVisual::Stopped => [239, 68, 68],
+impl Notifier for SystemNotifier {
+    fn notify(&self, notification: &Notification) {
+        let Some(app) = self.app.lock().ok().and_then(|guard| guard.clone()) else {
+            return;
         };
-        icon_from_color(color)
+        if let Err(err) = app
+            .notification()
+            .builder()
+            .title(&notification.title)
+            .body(&notification.body)
+            .show()
+        {
+            self.log.append(
+                "WARN",
+                &format!("Unable to show the {:?} notification: {err}", notification.category),
+            );
+        }
     }
 }
 
-fn icon_from_color(color: [u8; 3]) -> tauri::image::Image<'static> {
-    let size = 24usize;
-    let mut data = vec![0u8; size * size * 4];
-    for px in data.chunks_exact_mut(4) {
-        px[0] = color[0];
-        px[1] = color[1];
-        px[2] = color[2];
-        px[3] = 255;
-    }
-    tauri::image::Image::new_owned(data, size as u32, size as u32)
+fn tray_image(spec: &IconSpec) -> tauri::image::Image<'static> {
+    let raster = icon::render(spec);
+    tauri::image::Image::new_owned(raster.rgba, raster.width, raster.height)
+}
+
+/// The main window's scale factor, the closest thing to the tray's.
+fn tray_scale(app: &AppHandle) -> f64 {
+    app.get_webview_window("main")
+        .and_then(|window| window.scale_factor().ok())
+        .unwrap_or(1.0)
+}
+
+/// Template icons only mean something on macOS.
+fn template_icons(app: &AppHandle) -> bool {
+    cfg!(target_os = "macos")
+        && app
+            .config()
+            .app
+            .tray_icon
+            .as_ref()
+            .is_some_and(|tray| tray.icon_as_template)
 }
 
 fn format_duration(ms: u64) -> String {
@@ -327,11 +1131,13 @@ fn now_string() -> String {
     Utc::now().to_rfc3339()
 }
 
-fn detect_repo_root() -> PathBuf {
+/// Finds the directory holding the proxy sources, and names the probe that
+/// found it.
+fn detect_repo_root() -> (PathBuf, &'static str) {
     if let Ok(explicit_root) = env::var("ANTIGRAVITY_DESKTOP_ROOT") {
         let candidate = PathBuf::from(explicit_root);
         if repo_assets_present(&candidate) {
-            return candidate;
+            return (candidate, "ANTIGRAVITY_DESKTOP_ROOT");
         }
     }
 
@@ -343,15 +1149,15 @@ fn detect_repo_root() -> PathBuf {
         {
             let bundled_app_dir = resources_dir.join("resources").join("app");
             if repo_assets_present(&bundled_app_dir) {
-                return bundled_app_dir;
+                return (bundled_app_dir, "bundle Resources/resources/app");
             }
 
             let app_dir = resources_dir.join("app");
             if repo_assets_present(&app_dir) {
-                return app_dir;
+                return (app_dir, "bundle Resources/app");
             }
             if repo_assets_present(&resources_dir) {
-                return resources_dir;
+                return (resources_dir, "bundle Resources");
             }
         }
 
@@ -359,17 +1165,18 @@ fn detect_repo_root() -> PathBuf {
         while let Some(parent) = cursor.parent() {
             let candidate = parent.to_path_buf();
             if repo_assets_present(&candidate) {
-                return candidate;
+                return (candidate, "ancestor of the executable");
             }
             cursor = parent;
         }
     }
 
-    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    let fallback = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .parent()
         .and_then(|p| p.parent())
         .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("."))
+        .unwrap_or_else(|| PathBuf::from("."));
+    (fallback, "build directory (fallback)")
 }
 
 fn repo_assets_present(path: &Path) -> bool {
@@ -479,30 +1286,87 @@ fn latest_nvm_node_bin() -> Option<PathBuf> {
         .next()
 }
 
-fn spawn_line_reader<R>(state: ProxyState, reader: R, label: &'static str)
+fn spawn_stdout_reader<R>(state: ProxyState, reader: R)
 where
     R: AsyncRead + Unpin + Send + 'static,
 {
     tauri::async_runtime::spawn(async move {
         let mut lines = BufReader::new(reader).lines();
         while let Ok(Some(line)) = lines.next_line().await {
-            state.append_log(label, &line).await;
-            if label == "STDOUT" {
-                if let Ok(event) = serde_json::from_str::<ProxyEvent>(&line) {
-                    state.apply_event(event).await;
-                }
+            state.append_log("STDOUT", &line).await;
+            // Events are parsed from the raw line: snapshots need the real
+            // account emails. Only the message text is shown as is.
+            if let Ok(mut event) = serde_json::from_str::<ProxyEvent>(&line) {
+                event.message = event
+                    .message
+                    .map(|message| state.redactor.redact(&message).into_owned());
+                state.apply_event(event).await;
+            }
+        }
+    });
+}
+
+/// Logs stderr line by line, but classifies it in bursts: lines that arrive
+/// within the aggregation window of each other (a stack trace, a multi-line
+/// warning) make up one report.
+fn spawn_stderr_reader<R>(state: ProxyState, reader: R)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tauri::async_runtime::spawn(async move {
+        let window = Duration::from_millis(state.settings.stderr.aggregation_window_ms);
+        let mut lines = BufReader::new(reader).lines();
+        let mut burst: Vec<String> = Vec::new();
+        loop {
+            let next = if burst.is_empty() {
+                lines.next_line().await
             } else {
-                state
-                    .apply_event(ProxyEvent {
-                        event: "error".to_string(),
-                        phase: None,
-                        snapshot: None,
-                        message: Some(line.clone()),
-                        reason: None,
-                    })
-                    .await;
+                match timeout(window, lines.next_line()).await {
+                    Ok(next) => next,
+                    Err(_) => {
+                        state.report_stderr(std::mem::take(&mut burst)).await;
+                        continue;
+                    }
+                }
+            };
+            match next {
+                Ok(Some(line)) => {
+                    state.append_log("STDERR", &line).await;
+                    burst.push(state.redactor.redact(&line).into_owned());
+                }
+                _ => break,
             }
         }
+        if !burst.is_empty() {
+            state.report_stderr(burst).await;
+        }
+    });
+}
+
+/// Marks the daemon as a legacy backend if it has not said hello in time.
+fn spawn_handshake_timer(state: ProxyState, generation: u64) {
+    tauri::async_runtime::spawn(async move {
+        let wait = Duration::from_millis(state.settings.startup.handshake_timeout_ms);
+        sleep(wait).await;
+        let timed_out = {
+            let mut status = state.status.lock().await;
+            let pending = status.generation == generation
+                && status.running
+                && status.protocol == ProtocolState::Pending;
+            if pending {
+                status.protocol = ProtocolState::Degraded(format!(
+                    "Backend sent no hello within {}; assuming a legacy daemon",
+                    format_duration(wait.as_millis() as u64)
+                ));
+            }
+            pending
+        };
+        if timed_out {
+            state
+                .append_log("WARN", "Backend did not complete the handshake; running degraded")
+                .await;
+            state.publish().await;
+        }
     });
 }
 
@@ -513,14 +1377,14 @@ fn spawn_watchdog(state: ProxyState) {
                 let mut guard = state.child.lock().await;
                 if let Some(child) = guard.as_mut() {
                     match child.try_wait() {
-                        Ok(Some(_)) => {
+                        Ok(Some(exit)) => {
                             *guard = None;
-                            true
+                            Some(exit)
                         }
-                        Ok(None) => false,
+                        Ok(None) => None,
                         Err(err) => {
                             state.append_log("ERROR", &format!("watchdog: {err}")).await;
-                            false
+                            None
                         }
                     }
                 } else {
@@ -528,8 +1392,10 @@ fn spawn_watchdog(state: ProxyState) {
                 }
             };
 
-            if exited {
-                state.mark_stopped(Some("Proxy process exited")).await;
+            if let Some(exit) = exited {
+                state.control.detach().await;
+                state.forget_daemon();
+                state.handle_unexpected_exit(exit).await;
                 break;
             }
 
@@ -538,13 +1404,55 @@ fn spawn_watchdog(state: ProxyState) {
     });
 }
 
-async fn start_proxy_impl(state: &ProxyState) -> Result<UiStatus, String> {
+/// Watches an adopted daemon by pid and `/health` until it exits or is stopped.
+#[cfg(not(windows))]
+fn spawn_adopted_monitor(state: ProxyState, pid: u32) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if state.status.lock().await.adopted_pid != Some(pid) {
+                break;
+            }
+            if !pidfile::is_alive(pid) {
+                state.status.lock().await.adopted_pid = None;
+                state.forget_daemon();
+                let message = format!("Adopted proxy (pid {pid}) exited");
+                state.append_log("ERROR", &message).await;
+                state.notify(|notifications| notifications.daemon_exited(&message));
+                state.mark_stopped(Some(&message)).await;
+                break;
+            }
+
+            let port = state.active_port().await;
+            let (readiness, counts) = match health::probe(port, Duration::from_secs(2)).await {
+                Ok(report) => (report.readiness(), Some(report.counts)),
+                Err(err) => (Readiness::Degraded(format!("/health failed: {err}")), None),
+            };
+            let changed = {
+                let mut status = state.status.lock().await;
+                let changed = status.adopted_pid == Some(pid)
+                    && (status.readiness != readiness || status.health != counts);
+                if changed {
+                    status.readiness = readiness;
+                    status.health = counts;
+                    status.last_update = Some(now_string());
+                }
+                changed
+            };
+            if changed {
+                state.publish().await;
+            }
+
+            sleep(Duration::from_secs(3)).await;
+        }
+    });
+}
+
+/// Spawns `desktop/proxy-daemon.js` and wires up its readers and watchdog.
+/// Does nothing if a daemon is already running.
+async fn spawn_daemon(state: &ProxyState, port: u16) -> Result<(), String> {
     let mut guard = state.child.lock().await;
     if guard.is_some() {
-        drop(guard);
-        let mut ui = state.current_status().await;
-        ui.config = state.claude_config_status().await;
-        return Ok(ui);
+        return Ok(());
     }
 
     let script_path = state.repo_root().join("desktop/proxy-daemon.js");
@@ -558,85 +1466,240 @@ async fn start_proxy_impl(state: &ProxyState) -> Result<UiStatus, String> {
         .arg(&script_path)
         .current_dir(state.repo_root())
         .env("ANTIGRAVITY_HOST", "127.0.0.1")
+        .env("ANTIGRAVITY_PORT", port.to_string())
+        .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
 
+    // Lead a new process group so stopping the daemon also reaches anything it spawned.
+    #[cfg(unix)]
+    command.process_group(0);
+
+    // Reset before spawning so a fast `hello` cannot be overwritten.
+    state.phase.send_replace(None);
+    let generation = {
+        let mut status = state.status.lock().await;
+        status.generation += 1;
+        status.protocol = ProtocolState::Pending;
+        status.handshake = None;
+        status.readiness = Readiness::Starting;
+        status.health = None;
+        status.snapshot = None;
+        status.port = Some(port);
+        status.generation
+    };
+
     let mut child = command.spawn().map_err(|err| err.to_string())?;
+    if let Some(pid) = child.id() {
+        state.record_daemon(pid, port);
+    }
+
+    if let Some(stdin) = child.stdin.take() {
+        state.control.attach(stdin).await;
+    }
 
     if let Some(stdout) = child.stdout.take() {
-        spawn_line_reader(state.clone(), stdout, "STDOUT");
+        spawn_stdout_reader(state.clone(), stdout);
     }
 
     if let Some(stderr) = child.stderr.take() {
-        spawn_line_reader(state.clone(), stderr, "STDERR");
+        spawn_stderr_reader(state.clone(), stderr);
     }
 
     *guard = Some(child);
     drop(guard);
 
     spawn_watchdog(state.clone());
+    spawn_handshake_timer(state.clone(), generation);
+    Ok(())
+}
+
+/// Starts the daemon on `port`, or on the configured preference when `None`.
+async fn start_proxy_impl(state: &ProxyState, port: Option<u16>) -> Result<UiStatus, String> {
+    let adopted = state.status.lock().await.adopted_pid.is_some();
+    if adopted || state.child.lock().await.is_some() {
+        state.refresh_config().await;
+        let ui = state.current_status().await;
+        return Ok(ui);
+    }
+
+    let port = state
+        .choose_port(port.unwrap_or(state.settings.port.preferred))
+        .await?;
+    state.restart_policy.lock().await.reset();
+    spawn_daemon(state, port).await?;
 
     {
         let mut status = state.status.lock().await;
         status.running = true;
         status.last_error = None;
+        status.warnings.clear();
         status.last_update = Some(now_string());
+        status.restart_count = 0;
+        status.crash_loop = false;
+        status.restart_pending = false;
     }
-    let _ = state.update_tray().await;
+    state.publish().await;
+    state.settle_readiness().await?;
 
-    let mut ui = state.current_status().await;
-    ui.config = state.claude_config_status().await;
+    state.attach_on_start().await;
+    state.refresh_config().await;
+    let ui = state.current_status().await;
     Ok(ui)
 }
 
-async fn stop_proxy_impl(state: &ProxyState) -> Result<UiStatus, String> {
-    {
-        let mut guard = state.child.lock().await;
-        if let Some(mut child) = guard.take() {
-            #[cfg(not(windows))]
-            {
-                use nix::sys::signal::{kill, Signal};
-                use nix::unistd::Pid;
+/// Stops `child` through [`supervisor::stop_group`], with `stopping`/`stopped`
+/// on the daemon's stdout counting as the acknowledgement.
+#[cfg(not(windows))]
+async fn terminate_daemon(state: &ProxyState, mut child: Child) -> StopReport {
+    supervisor::stop_group(&mut child, &state.settings.stop, state.phase.subscribe()).await
+}
 
-                if let Some(pid) = child.id() {
-                    let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
-                }
+/// Stops a daemon this shell did not spawn: SIGTERM its process group, poll
+/// until the pid is gone, and SIGKILL the group if it outlives the grace period.
+#[cfg(not(windows))]
+async fn terminate_pid(state: &ProxyState, pid: u32) -> StopReport {
+    use nix::sys::signal::Signal;
+    use supervisor::signal_process_group;
+
+    async fn wait_gone(pid: u32, limit_ms: u64) -> bool {
+        let deadline = Instant::now() + Duration::from_millis(limit_ms);
+        while pidfile::is_alive(pid) {
+            if Instant::now() >= deadline {
+                return false;
             }
+            sleep(Duration::from_millis(100)).await;
+        }
+        true
+    }
 
-            #[cfg(windows)]
-            {
-                child.kill().await.map_err(|err| err.to_string())?;
-            }
+    let started = Instant::now();
+    let timeouts = &state.settings.stop;
 
-            let _ = child.wait().await;
+    signal_process_group(Some(pid), Signal::SIGTERM);
+    let outcome = if wait_gone(pid, timeouts.ack_timeout_ms + timeouts.grace_timeout_ms).await {
+        StopOutcome::Graceful
+    } else {
+        signal_process_group(Some(pid), Signal::SIGKILL);
+        if wait_gone(pid, timeouts.kill_timeout_ms).await {
+            StopOutcome::Killed
         } else {
-            drop(guard);
-            let mut ui = state.current_status().await;
-            ui.config = state.claude_config_status().await;
-            return Ok(ui);
+            StopOutcome::Unresponsive
         }
+    };
+    signal_process_group(Some(pid), Signal::SIGKILL);
+
+    StopReport {
+        outcome,
+        acknowledged: false,
+        exit_code: None,
+        elapsed_ms: started.elapsed().as_millis() as u64,
     }
+}
+
+#[cfg(windows)]
+async fn terminate_daemon(state: &ProxyState, mut child: Child) -> StopReport {
+    let started = Instant::now();
+    let _ = child.start_kill();
+    let waited = timeout(
+        Duration::from_millis(state.settings.stop.kill_timeout_ms),
+        child.wait(),
+    )
+    .await;
+    let (outcome, exit_code) = match waited {
+        Ok(Ok(status)) => (StopOutcome::Killed, status.code()),
+        _ => (StopOutcome::Unresponsive, None),
+    };
+    StopReport {
+        outcome,
+        acknowledged: false,
+        exit_code,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
 
-    state.mark_stopped(None).await;
-    let mut ui = state.current_status().await;
-    ui.config = state.claude_config_status().await;
+async fn stop_proxy_impl(state: &ProxyState) -> Result<UiStatus, String> {
+    let cancelled_restart = std::mem::take(&mut state.status.lock().await.restart_pending);
+
+    let report = {
+        let mut guard = state.child.lock().await;
+        let adopted = state.status.lock().await.adopted_pid.take();
+        match (guard.take(), adopted) {
+            (Some(child), _) => terminate_daemon(state, child).await,
+            #[cfg(not(windows))]
+            (None, Some(pid)) => terminate_pid(state, pid).await,
+            _ => StopReport::not_running(),
+        }
+    };
+    state.control.detach().await;
+    if report.outcome != StopOutcome::NotRunning {
+        state.forget_daemon();
+    }
+
+    if report.outcome != StopOutcome::NotRunning {
+        let level = if report.outcome == StopOutcome::Graceful {
+            "INFO"
+        } else {
+            "ERROR"
+        };
+        state.append_log(level, &report.describe()).await;
+    }
+
+    let error = (report.outcome == StopOutcome::Unresponsive).then(|| report.describe());
+    let should_mark = report.outcome != StopOutcome::NotRunning || cancelled_restart;
+    state.status.lock().await.last_stop = Some(report);
+    if should_mark {
+        state.mark_stopped(error.as_deref()).await;
+    }
+
+    state.refresh_config().await;
+    let ui = state.current_status().await;
     Ok(ui)
 }
 
+// `Shell::open` is deprecated in favour of tauri-plugin-opener; keep it until we migrate.
+#[allow(deprecated)]
 async fn open_dashboard_impl(app: &AppHandle, state: &ProxyState) -> Result<(), String> {
-    let status = state.status.lock().await.clone();
-    let port = status
-        .snapshot
-        .as_ref()
-        .and_then(|s| s.get("port").and_then(|p| p.as_i64()))
-        .unwrap_or(8080);
-    drop(status);
+    let port = state.active_port().await;
     let url = format!("http://localhost:{port}/dashboard");
     app.shell().open(&url, None).map_err(|err: tauri_plugin_shell::Error| err.to_string())
 }
 
-async fn view_logs_impl(app: &AppHandle, state: &ProxyState) -> Result<(), String> {
-    let path = state.log_path().to_path_buf();
+/// The live log, or one of its rotated copies when `requested` names it.
+fn resolve_log(state: &ProxyState, requested: Option<&str>) -> Result<PathBuf, String> {
+    match requested {
+        None => Ok(state.log_path().to_path_buf()),
+        Some(requested) => logging::list_logs(state.log_path())
+            .into_iter()
+            .map(|file| file.path)
+            .find(|path| path.as_os_str() == requested)
+            .ok_or_else(|| format!("{requested} is not one of the desktop logs")),
+    }
+}
+
+/// Runs a log query off the async runtime, after pushing buffered lines to
+/// disk so the result lines up with the `proxy://log` events already sent.
+async fn query_log<F>(state: &ProxyState, requested: Option<&str>, query: F) -> Result<LogPage, String>
+where
+    F: FnOnce(&Path) -> Result<LogPage, String> + Send + 'static,
+{
+    let path = resolve_log(state, requested)?;
+    let log = state.log.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        log.flush(Duration::from_millis(500));
+        query(&path)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+}
+
+#[allow(deprecated)]
+async fn view_logs_impl(
+    app: &AppHandle,
+    state: &ProxyState,
+    requested: Option<&str>,
+) -> Result<(), String> {
+    let path = resolve_log(state, requested)?;
     if !path.exists() {
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent).map_err(|err| err.to_string())?;
@@ -649,9 +1712,120 @@ async fn view_logs_impl(app: &AppHandle, state: &ProxyState) -> Result<(), Strin
         .map_err(|err: tauri_plugin_shell::Error| err.to_string())
 }
 
+/// Collects logs, redacted proxy config, Claude settings status, Node and
+/// repo detection, the last snapshot and versions into a `.tar.gz`. Without
+/// `path` the user picks the destination; `None` means they cancelled.
+async fn export_diagnostics_impl(
+    app: &AppHandle,
+    state: &ProxyState,
+    path: Option<String>,
+) -> Result<Option<String>, String> {
+    let mut bundle = Bundle::new();
+    let path = match path {
+        Some(path) => expand_tilde(&path),
+        None => {
+            let (tx, rx) = tokio::sync::oneshot::channel();
+            let mut dialog = app
+                .dialog()
+                .file()
+                .set_title("Export diagnostics")
+                .set_file_name(bundle.file_name())
+                .add_filter("Compressed archive", &["gz", "tgz"]);
+            if let Some(downloads) = dirs::download_dir() {
+                dialog = dialog.set_directory(downloads);
+            }
+            dialog.save_file(move |chosen| {
+                let _ = tx.send(chosen);
+            });
+            match rx.await.ok().flatten() {
+                Some(chosen) => chosen.into_path().map_err(|err| err.to_string())?,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    // Redact regardless of the user's logging preference.
+    let (redactor, _) = Redactor::new(&RedactSettings {
+        enabled: true,
+        ..state.settings.redact.clone()
+    });
+
+    let proxy_config = home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".config/antigravity-proxy/config.json");
+    match fs::read_to_string(&proxy_config) {
+        Ok(text) => bundle.add(
+            "proxy-config.json",
+            redactor.redact(&text).into_owned().into_bytes(),
+        ),
+        Err(err) => bundle.add(
+            "proxy-config.json.missing",
+            format!("{}: {err}\n", proxy_config.display()).into_bytes(),
+        ),
+    }
+
+    let claude_config = state.refresh_config().await;
+    bundle.add_json("claude-config-status.json", &claude_config, &redactor);
+
+    let node = match resolve_node_binary() {
+        Ok(node_bin) => {
+            let version = match timeout(
+                Duration::from_secs(5),
+                Command::new(&node_bin).arg("--version").output(),
+            )
+            .await
+            {
+                Ok(Ok(output)) => json!(String::from_utf8_lossy(&output.stdout).trim()),
+                Ok(Err(err)) => json!({ "error": err.to_string() }),
+                Err(_) => json!({ "error": "node --version timed out" }),
+            };
+            json!({ "binary": node_bin, "version": version })
+        }
+        Err(err) => json!({ "error": err }),
+    };
+
+    let status = state.current_status().await;
+    bundle.add_json(
+        "environment.json",
+        &json!({
+            "appVersion": env!("CARGO_PKG_VERSION"),
+            "os": env::consts::OS,
+            "arch": env::consts::ARCH,
+            "exportedAt": now_string(),
+            "repoRoot": state.repo_root(),
+            "repoRootProbe": state.repo_probe,
+            "node": node,
+            "logPath": state.log_path(),
+            "settings": &*state.settings,
+        }),
+        &redactor,
+    );
+    bundle.add_json("snapshot.json", &status.snapshot, &redactor);
+    bundle.add_json("status.json", &status, &redactor);
+
+    let log = state.log.clone();
+    let written = path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        log.flush(Duration::from_secs(1));
+        bundle.add_log_tails(log.path(), &redactor);
+        bundle.write(&written)
+    })
+    .await
+    .map_err(|err| err.to_string())?
+    .map_err(|err| format!("Unable to write {}: {err}", path.display()))?;
+
+    state
+        .append_log("INFO", &format!("Exported diagnostics to {}", path.display()))
+        .await;
+    Ok(Some(path.display().to_string()))
+}
+
 #[tauri::command]
-async fn start_proxy(state: State<'_, ProxyState>) -> Result<UiStatus, String> {
-    start_proxy_impl(&state).await
+async fn start_proxy(
+    port: Option<u16>,
+    state: State<'_, ProxyState>,
+) -> Result<UiStatus, String> {
+    start_proxy_impl(&state, port).await
 }
 
 #[tauri::command]
@@ -661,62 +1835,227 @@ async fn stop_proxy(state: State<'_, ProxyState>) -> Result<UiStatus, String> {
 
 #[tauri::command]
 async fn fetch_status(state: State<'_, ProxyState>) -> Result<UiStatus, String> {
-    let mut ui = state.current_status().await;
-    ui.config = state.claude_config_status().await;
+    state.refresh_config().await;
+    let ui = state.current_status().await;
     Ok(ui)
 }
 
+#[tauri::command]
+async fn switch_account(email: String, state: State<'_, ProxyState>) -> Result<UiStatus, String> {
+    state.switch_account(&email).await?;
+    Ok(state.current_status().await)
+}
+
+#[tauri::command]
+async fn reload_proxy_config(state: State<'_, ProxyState>) -> Result<Value, String> {
+    state.reload_proxy_config().await
+}
+
+#[tauri::command]
+async fn flush_flows(state: State<'_, ProxyState>) -> Result<u64, String> {
+    state.flush_flows().await
+}
+
+#[tauri::command]
+async fn refresh_snapshot(state: State<'_, ProxyState>) -> Result<UiStatus, String> {
+    state.request_status().await?;
+    Ok(state.current_status().await)
+}
+
 #[tauri::command]
 async fn open_dashboard(app: AppHandle, state: State<'_, ProxyState>) -> Result<(), String> {
     open_dashboard_impl(&app, &state).await
 }
 
 #[tauri::command]
-async fn view_logs(app: AppHandle, state: State<'_, ProxyState>) -> Result<(), String> {
-    view_logs_impl(&app, &state).await
+async fn view_logs(
+    path: Option<String>,
+    app: AppHandle,
+    state: State<'_, ProxyState>,
+) -> Result<(), String> {
+    view_logs_impl(&app, &state, path.as_deref()).await
+}
+
+#[tauri::command]
+async fn list_logs(state: State<'_, ProxyState>) -> Result<Vec<LogFile>, String> {
+    Ok(logging::list_logs(state.log_path()))
+}
+
+/// Pages backwards through a log: the newest `limit` entries matching
+/// `filter` that end before byte offset `before`.
+#[tauri::command]
+async fn read_logs(
+    path: Option<String>,
+    before: Option<u64>,
+    limit: Option<usize>,
+    filter: Option<LogFilter>,
+    state: State<'_, ProxyState>,
+) -> Result<LogPage, String> {
+    let filter = filter.unwrap_or_default();
+    query_log(&state, path.as_deref(), move |path| {
+        logview::read_page(path, before, limit, &filter)
+    })
+    .await
+}
+
+/// Entries of the live log appended since byte offset `from`.
+#[tauri::command]
+async fn tail_logs(
+    from: u64,
+    filter: Option<LogFilter>,
+    state: State<'_, ProxyState>,
+) -> Result<LogPage, String> {
+    let filter = filter.unwrap_or_default();
+    query_log(&state, None, move |path| logview::tail(path, from, &filter)).await
+}
+
+#[tauri::command]
+async fn export_diagnostics(
+    path: Option<String>,
+    app: AppHandle,
+    state: State<'_, ProxyState>,
+) -> Result<Option<String>, String> {
+    export_diagnostics_impl(&app, &state, path).await
+}
+
+#[tauri::command]
+async fn repair_claude_config(
+    keys: Option<Vec<String>>,
+    state: State<'_, ProxyState>,
+) -> Result<ClaudeConfigStatus, String> {
+    let repaired = state.repair_claude_config(keys.as_deref()).await;
+    state.refresh_config().await;
+    state.publish().await;
+    repaired
+}
+
+#[tauri::command]
+async fn detach_claude_config(state: State<'_, ProxyState>) -> Result<ClaudeConfigStatus, String> {
+    let detached = state.detach_claude_config().await;
+    state.refresh_config().await;
+    state.publish().await;
+    detached
+}
+
+#[tauri::command]
+async fn restore_claude_config(
+    name: String,
+    state: State<'_, ProxyState>,
+) -> Result<ClaudeConfigStatus, String> {
+    let restored = state.restore_claude_config(&name).await;
+    state.refresh_config().await;
+    state.publish().await;
+    restored
 }
 
 #[tauri::command]
-async fn repair_claude_config(state: State<'_, ProxyState>) -> Result<ClaudeConfigStatus, String> {
-    state.repair_claude_config().await
+async fn list_claude_backups() -> Result<Vec<ConfigBackup>, String> {
+    Ok(claude::list_backups(&claude::backup_dir()))
 }
 
 #[tauri::command]
 async fn check_claude_config(state: State<'_, ProxyState>) -> Result<ClaudeConfigStatus, String> {
-    state
-        .claude_config_status()
-        .await
-        .ok_or_else(|| "Unable to read Claude settings".to_string())
+    let config = state.refresh_config().await;
+    state.publish().await;
+    Ok(config)
+}
+
+/// Writes to `desktop.log` from a launch that exits before it has a
+/// `ProxyState`. Rotation is left to the running instance, which owns the file.
+fn log_without_state(level: &str, line: &str) {
+    let (settings, _) = DesktopSettings::load(&settings::settings_path());
+    let log = LogWriter::spawn(
+        settings::state_dir().join("desktop.log"),
+        LogSettings {
+            max_bytes: 0,
+            daily: false,
+            ..settings.log
+        },
+    );
+    log.append(level, line);
+    log.flush(Duration::from_secs(2));
+}
+
+fn focus_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // A second launch hands its arguments to the running instance and exits
+    // before creating another tray or `ProxyState`.
+    let (instance_guard, instance_error) = match instance::claim(&settings::state_dir()) {
+        Ok(Claim::Primary(guard)) => (Some(guard), None),
+        Ok(Claim::Secondary(forwarded)) => {
+            if let Err(err) = forwarded {
+                log_without_state("ERROR", &format!("Antigravity Desktop is already running: {err}"));
+            }
+            return;
+        }
+        Err(err) => (
+            None,
+            Some(format!("Single-instance check failed, continuing anyway: {err}")),
+        ),
+    };
+
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .plugin(tauri_plugin_notification::init())
         .manage(ProxyState::new())
         .invoke_handler(tauri::generate_handler![
             start_proxy,
             stop_proxy,
             fetch_status,
+            switch_account,
+            reload_proxy_config,
+            flush_flows,
+            refresh_snapshot,
             open_dashboard,
             view_logs,
+            list_logs,
+            read_logs,
+            tail_logs,
+            export_diagnostics,
             repair_claude_config,
+            detach_claude_config,
+            restore_claude_config,
+            list_claude_backups,
             check_claude_config
         ])
         .setup(|app| {
             let state = app.state::<ProxyState>().inner().clone();
 
-            // Create tray menu
-            let start_i = MenuItem::with_id(app, "start-proxy", "Start Proxy", true, None::<&str>)?;
-            let stop_i = MenuItem::with_id(app, "stop-proxy", "Stop Proxy", true, None::<&str>)?;
-            let dashboard_i = MenuItem::with_id(app, "open-dashboard", "Open Dashboard", true, None::<&str>)?;
-            let logs_i = MenuItem::with_id(app, "view-logs", "View Logs", true, None::<&str>)?;
-            let quit_i = MenuItem::with_id(app, "quit-app", "Quit", true, None::<&str>)?;
-
-            let menu = Menu::with_items(app, &[&start_i, &stop_i, &dashboard_i, &logs_i, &quit_i])?;
+            // `update_tray` rebuilds the menu as the daemon reports accounts.
+            let model = MenuModel::new(&TrayInput {
+                running: false,
+                starting: false,
+                port: state.settings.port.preferred,
+                snapshot: None,
+                last_error: None,
+                config_healthy: None,
+                now_ms: 0,
+            });
+            let menu = model.build(app.handle())?;
+            if let Ok(mut shown) = state.menu_model.lock() {
+                *shown = Some(model);
+            }
 
             let tray_state = state.clone();
+            let icon_spec = IconSpec {
+                visual: TrayVisual::Stopped,
+                badge: None,
+                size: icon::size_for_scale(tray_scale(app.handle())),
+                template: template_icons(app.handle()),
+            };
             let tray = TrayIconBuilder::new()
+                .icon(tray_image(&icon_spec))
+                .icon_as_template(icon_spec.template)
                 .menu(&menu)
                 .on_menu_event(move |app, event| {
                     let state_clone = tray_state.clone();
@@ -724,7 +2063,7 @@ pub fn run() {
                     match event.id.as_ref() {
                         "start-proxy" => {
                             tauri::async_runtime::spawn(async move {
-                                let _ = start_proxy_impl(&state_clone).await;
+                                let _ = start_proxy_impl(&state_clone, None).await;
                             });
                         }
                         "stop-proxy" => {
@@ -737,29 +2076,104 @@ pub fn run() {
                                 let _ = open_dashboard_impl(&handle_clone, &state_clone).await;
                             });
                         }
+                        "copy-base-url" => {
+                            tauri::async_runtime::spawn(async move {
+                                let url = format!("http://localhost:{}", state_clone.active_port().await);
+                                if let Err(err) = handle_clone.clipboard().write_text(url) {
+                                    state_clone
+                                        .append_log("ERROR", &format!("Unable to copy the base URL: {err}"))
+                                        .await;
+                                }
+                            });
+                        }
+                        "repair-config" => {
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(err) = state_clone.repair_claude_config(None).await {
+                                    state_clone
+                                        .append_log("ERROR", &format!("Unable to repair Claude settings: {err}"))
+                                        .await;
+                                }
+                                state_clone.refresh_config().await;
+                                state_clone.publish().await;
+                            });
+                        }
                         "view-logs" => {
                             tauri::async_runtime::spawn(async move {
-                                let _ = view_logs_impl(&handle_clone, &state_clone).await;
+                                let _ = view_logs_impl(&handle_clone, &state_clone, None).await;
                             });
                         }
-                        "quit-app" => {
-                            app.exit(0);
+                        "quit-app" => app.exit(0),
+                        id => {
+                            let Some(email) = tray::account_email(id).map(str::to_string) else {
+                                return;
+                            };
+                            // The click toggled the check mark natively; drop the
+                            // cached model so the next publish redraws it from the
+                            // daemon's answer.
+                            state_clone.invalidate_menu();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(err) = state_clone.switch_account(&email).await {
+                                    state_clone
+                                        .append_log("ERROR", &format!("Unable to switch to {email}: {err}"))
+                                        .await;
+                                    state_clone.publish().await;
+                                }
+                            });
                         }
-                        _ => {}
                     }
                 })
                 .build(app)?;
+            if let Ok(mut shown) = state.icon_spec.lock() {
+                *shown = Some(icon_spec);
+            }
             state.attach_tray(tray);
+            state.attach_app(app.handle().clone());
+
+            if let Some(guard) = instance_guard {
+                let handle = app.handle().clone();
+                let instance_state = state.clone();
+                guard.listen(move |forwarded| {
+                    focus_main_window(&handle);
+                    instance_state.emit(INSTANCE_EVENT, forwarded.clone());
+                    let state = instance_state.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let message = format!(
+                            "Another launch was forwarded to this instance (args: {:?})",
+                            forwarded.args
+                        );
+                        state.append_log("INFO", &message).await;
+                    });
+                });
+            }
+            if let Some(message) = instance_error {
+                let state = state.clone();
+                tauri::async_runtime::spawn(async move {
+                    state.append_log("ERROR", &message).await;
+                });
+            }
 
             let state_for_tray = state.clone();
             tauri::async_runtime::spawn(async move {
-                let _ = state_for_tray.update_tray().await;
+                #[cfg(not(windows))]
+                state_for_tray.recover_orphan().await;
+                state_for_tray.reattach_on_start().await;
+                state_for_tray.refresh_config().await;
+                state_for_tray.publish().await;
             });
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Every way out (tray Quit, Cmd+Q, the last window closing, logout)
+            // ends here.
+            if let RunEvent::Exit = event {
+                let state = app.state::<ProxyState>();
+                state.detach_on_quit();
+                state.log.flush(Duration::from_secs(2));
+            }
+        });
 }
 
 fn main() {
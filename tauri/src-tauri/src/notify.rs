@@ -0,0 +1,306 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use chrono::{Local, TimeZone};
+
+use crate::{settings::NotifySettings, snapshot::DaemonSnapshot};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Crash,
+    RateLimited,
+    InvalidAccount,
+    ConfigDrift,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub category: Category,
+    pub title: String,
+    pub body: String,
+}
+
+/// Delivers notifications. The shell shows them through the OS; anything
+/// else (a stub that records them, say) only has to implement this.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, notification: &Notification);
+}
+
+/// Turns changes in the shell's view of the daemon into notifications.
+///
+/// Only transitions notify: the first snapshot and the first config check set
+/// the baseline. A notification with the same category and subject as one
+/// sent within the cooldown is dropped.
+pub struct Notifications {
+    notifier: Box<dyn Notifier>,
+    settings: NotifySettings,
+    sent: HashMap<(Category, String), Instant>,
+    /// `None` until the first snapshot.
+    all_rate_limited: Option<bool>,
+    invalid: Option<HashSet<String>>,
+    config_healthy: Option<bool>,
+}
+
+impl Notifications {
+    pub fn new(notifier: Box<dyn Notifier>, settings: NotifySettings) -> Self {
+        Self {
+            notifier,
+            settings,
+            sent: HashMap::new(),
+            all_rate_limited: None,
+            invalid: None,
+            config_healthy: None,
+        }
+    }
+
+    pub fn daemon_exited(&mut self, message: &str) {
+        self.send(Category::Crash, "", "Proxy stopped unexpectedly", message.to_string());
+    }
+
+    pub fn observe_snapshot(&mut self, snapshot: &DaemonSnapshot) {
+        let usable: Vec<_> = snapshot.accounts.iter().filter(|account| !account.is_invalid).collect();
+        let all_limited = !usable.is_empty() && usable.iter().all(|account| account.is_rate_limited);
+        if all_limited && self.all_rate_limited == Some(false) {
+            let next = usable
+                .iter()
+                .filter_map(|account| account.next_available_at.or(account.rate_limit_reset_time))
+                .min()
+                .and_then(|ms| Local.timestamp_millis_opt(ms).single());
+            let mut body = match usable.as_slice() {
+                [only] => format!("{} is rate limited.", only.email),
+                _ => format!("All {} accounts are rate limited.", usable.len()),
+            };
+            if let Some(at) = next {
+                body.push_str(&format!(" The first frees up at {}.", at.format("%H:%M")));
+            }
+            self.send(Category::RateLimited, "", "Every account is rate limited", body);
+        }
+        self.all_rate_limited = Some(all_limited);
+
+        let invalid: HashSet<String> = snapshot
+            .accounts
+            .iter()
+            .filter(|account| account.is_invalid)
+            .map(|account| account.email.clone())
+            .collect();
+        if let Some(previous) = self.invalid.take() {
+            let mut fresh: Vec<_> = snapshot
+                .accounts
+                .iter()
+                .filter(|account| account.is_invalid && !previous.contains(&account.email))
+                .collect();
+            fresh.sort_by(|a, b| a.email.cmp(&b.email));
+            for account in fresh {
+                let body = match account.invalid_reason.as_deref() {
+                    Some(reason) => format!("{}: {reason}", account.email),
+                    None => format!("{} needs to be signed in again.", account.email),
+                };
+                self.send(Category::InvalidAccount, &account.email, "Account invalid", body);
+            }
+        }
+        self.invalid = Some(invalid);
+
+        if let Some(config) = &snapshot.claude_config {
+            self.observe_config(config.healthy, config.settings_path.as_deref().unwrap_or("settings.json"));
+        }
+    }
+
+    /// Fed by the shell's own checks as well as the daemon's snapshots.
+    pub fn observe_config(&mut self, healthy: bool, settings_path: &str) {
+        if !healthy && self.config_healthy == Some(true) {
+            self.send(
+                Category::ConfigDrift,
+                settings_path,
+                "Claude settings changed",
+                format!("{settings_path} no longer points Claude at the proxy."),
+            );
+        }
+        self.config_healthy = Some(healthy);
+    }
+
+    /// Forgets the last config check, so a change the user asked for (a
+    /// detach or restore) is not reported as drift.
+    pub fn reset_config(&mut self) {
+        self.config_healthy = None;
+    }
+
+    fn enabled(&self, category: Category) -> bool {
+        self.settings.enabled
+            && match category {
+                Category::Crash => self.settings.crash,
+                Category::RateLimited => self.settings.rate_limited,
+                Category::InvalidAccount => self.settings.invalid_account,
+                Category::ConfigDrift => self.settings.config_drift,
+            }
+    }
+
+    fn send(&mut self, category: Category, subject: &str, title: &str, body: String) {
+        if !self.enabled(category) {
+            return;
+        }
+        let now = Instant::now();
+        let cooldown = Duration::from_secs(self.settings.cooldown_secs);
+        let key = (category, subject.to_string());
+        if self.sent.get(&key).is_some_and(|last| now.duration_since(*last) < cooldown) {
+            return;
+        }
+        self.sent.insert(key, now);
+        self.notifier.notify(&Notification {
+            category,
+            title: title.to_string(),
+            body,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::snapshot::AccountSnapshot;
+
+    /// Keeps every notification so tests can look at what was sent.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<Notification>>>);
+
+    impl Notifier for Recorder {
+        fn notify(&self, notification: &Notification) {
+            self.0.lock().unwrap().push(notification.clone());
+        }
+    }
+
+    impl Recorder {
+        fn categories(&self) -> Vec<Category> {
+            self.0.lock().unwrap().drain(..).map(|sent| sent.category).collect()
+        }
+    }
+
+    fn recording(settings: NotifySettings) -> (Notifications, Recorder) {
+        let recorder = Recorder::default();
+        (Notifications::new(Box::new(recorder.clone()), settings), recorder)
+    }
+
+    fn account(email: &str, rate_limited: bool, invalid: bool) -> AccountSnapshot {
+        AccountSnapshot {
+            email: email.to_string(),
+            is_rate_limited: rate_limited,
+            is_invalid: invalid,
+            ..AccountSnapshot::default()
+        }
+    }
+
+    fn snapshot(accounts: Vec<AccountSnapshot>) -> DaemonSnapshot {
+        DaemonSnapshot {
+            accounts,
+            ..DaemonSnapshot::default()
+        }
+    }
+
+    #[test]
+    fn crash_notifies_every_time_outside_the_cooldown() {
+        let (mut notifications, recorder) = recording(NotifySettings {
+            cooldown_secs: 0,
+            ..NotifySettings::default()
+        });
+        notifications.daemon_exited("exit code 1");
+        notifications.daemon_exited("exit code 1");
+        assert_eq!(recorder.categories(), [Category::Crash, Category::Crash]);
+    }
+
+    #[test]
+    fn exhaustion_notifies_on_the_transition_only() {
+        let (mut notifications, recorder) = recording(NotifySettings::default());
+        let limited = snapshot(vec![account("a@x", true, false), account("b@x", true, false)]);
+        // The first snapshot is only a baseline.
+        notifications.observe_snapshot(&limited);
+        assert!(recorder.categories().is_empty());
+
+        let fine = snapshot(vec![account("a@x", true, false), account("b@x", false, false)]);
+        notifications.observe_snapshot(&fine);
+        notifications.observe_snapshot(&limited);
+        notifications.observe_snapshot(&limited);
+        assert_eq!(recorder.categories(), [Category::RateLimited]);
+    }
+
+    #[test]
+    fn invalid_accounts_notify_once_each() {
+        let (mut notifications, recorder) = recording(NotifySettings::default());
+        notifications.observe_snapshot(&snapshot(vec![account("a@x", false, true)]));
+        notifications.observe_snapshot(&snapshot(vec![
+            account("a@x", false, true),
+            account("b@x", false, true),
+            account("c@x", false, true),
+        ]));
+        let sent = recorder.0.lock().unwrap().clone();
+        let bodies: Vec<_> = sent.iter().map(|sent| sent.body.as_str()).collect();
+        assert_eq!(
+            bodies,
+            ["b@x needs to be signed in again.", "c@x needs to be signed in again."]
+        );
+    }
+
+    #[test]
+    fn config_drift_notifies_when_it_turns_unhealthy() {
+        let (mut notifications, recorder) = recording(NotifySettings::default());
+        notifications.observe_config(false, "settings.json");
+        notifications.observe_config(true, "settings.json");
+        notifications.observe_config(false, "settings.json");
+        notifications.observe_config(false, "settings.json");
+        assert_eq!(recorder.categories(), [Category::ConfigDrift]);
+    }
+
+    #[test]
+    fn reset_config_swallows_the_next_change() {
+        let (mut notifications, recorder) = recording(NotifySettings::default());
+        notifications.observe_config(true, "settings.json");
+        notifications.reset_config();
+        notifications.observe_config(false, "settings.json");
+        assert!(recorder.categories().is_empty());
+    }
+
+    #[test]
+    fn disabled_categories_stay_quiet() {
+        let (mut notifications, recorder) = recording(NotifySettings {
+            crash: false,
+            config_drift: false,
+            ..NotifySettings::default()
+        });
+        notifications.daemon_exited("exit code 1");
+        notifications.observe_config(true, "settings.json");
+        notifications.observe_config(false, "settings.json");
+        notifications.observe_snapshot(&snapshot(vec![]));
+        notifications.observe_snapshot(&snapshot(vec![account("a@x", false, true)]));
+        assert_eq!(recorder.categories(), [Category::InvalidAccount]);
+
+        let (mut notifications, recorder) = recording(NotifySettings {
+            enabled: false,
+            ..NotifySettings::default()
+        });
+        notifications.daemon_exited("exit code 1");
+        assert!(recorder.categories().is_empty());
+    }
+
+    #[test]
+    fn cooldown_suppresses_repeats_per_subject() {
+        let (mut notifications, recorder) = recording(NotifySettings::default());
+        notifications.daemon_exited("exit code 1");
+        notifications.daemon_exited("exit code 1");
+        assert_eq!(recorder.categories(), [Category::Crash]);
+
+        let healthy = snapshot(vec![]);
+        let one = snapshot(vec![account("a@x", false, true)]);
+        let both = snapshot(vec![account("a@x", false, true), account("b@x", false, true)]);
+        notifications.observe_snapshot(&healthy);
+        notifications.observe_snapshot(&one);
+        notifications.observe_snapshot(&healthy);
+        notifications.observe_snapshot(&both);
+        // a@x went invalid twice within the cooldown; b@x is a new subject.
+        assert_eq!(
+            recorder.categories(),
+            [Category::InvalidAccount, Category::InvalidAccount]
+        );
+    }
+}
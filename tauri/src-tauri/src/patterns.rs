@@ -0,0 +1,17 @@
+use regex::Regex;
+
+/// Compiles user-supplied patterns from the settings file. Patterns that fail
+/// to compile are skipped, with a message for the log pushed onto `errors`;
+/// `what` names the setting in that message.
+pub fn compile_user(patterns: &[String], what: &str, errors: &mut Vec<String>) -> Vec<Regex> {
+    patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(err) => {
+                errors.push(format!("Ignoring {what} pattern {pattern:?}: {err}"));
+                None
+            }
+        })
+        .collect()
+}
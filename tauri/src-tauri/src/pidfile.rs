@@ -0,0 +1,214 @@
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// What the shell records about the daemon it spawned, so a later launch can
+/// find it again if this one dies without stopping it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonRecord {
+    pub pid: u32,
+    pub port: u16,
+    pub started_at: String,
+    /// Pid of the desktop shell that spawned the daemon.
+    pub shell_pid: u32,
+}
+
+/// `daemon.json` plus the `daemon.lock` that marks a live shell as its owner.
+///
+/// The lock is held for as long as this value lives. A launch that obtains
+/// it while `daemon.json` still names a running process has found an orphan.
+pub struct PidFile {
+    path: PathBuf,
+    #[cfg(not(windows))]
+    _lock: nix::fcntl::Flock<fs::File>,
+}
+
+impl PidFile {
+    /// Takes the lock in `dir`. Fails if another shell already holds it.
+    #[cfg(not(windows))]
+    pub fn acquire(dir: &Path) -> Result<Self, String> {
+        use nix::fcntl::{Flock, FlockArg};
+
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        let lock_path = dir.join("daemon.lock");
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .map_err(|err| format!("Unable to open {}: {err}", lock_path.display()))?;
+        let lock = Flock::lock(file, FlockArg::LockExclusiveNonblock).map_err(|(_, errno)| {
+            format!(
+                "{} is held by another instance ({errno})",
+                lock_path.display()
+            )
+        })?;
+        Ok(Self {
+            path: dir.join("daemon.json"),
+            _lock: lock,
+        })
+    }
+
+    /// Orphan recovery needs a pid that can be checked and signalled, which
+    /// this module only does on unix, so Windows never records a daemon.
+    #[cfg(windows)]
+    pub fn acquire(_dir: &Path) -> Result<Self, String> {
+        Err("Orphan recovery is not supported on Windows; a proxy left running by a crash must be stopped by hand".to_string())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the record left by the previous daemon, if any. A corrupt file
+    /// is reported so the caller can log it and clear it.
+    pub fn read(&self) -> Result<Option<DaemonRecord>, String> {
+        match fs::read_to_string(&self.path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .map(Some)
+                .map_err(|err| format!("Ignoring invalid {}: {err}", self.path.display())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("Unable to read {}: {err}", self.path.display())),
+        }
+    }
+
+    pub fn write(&self, record: &DaemonRecord) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(record).map_err(|err| err.to_string())?;
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json)
+            .and_then(|()| fs::rename(&tmp, &self.path))
+            .map_err(|err| format!("Unable to write {}: {err}", self.path.display()))
+    }
+
+    pub fn clear(&self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// What a recorded pid turned out to be on the next launch.
+#[cfg(not(windows))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Liveness {
+    /// No such process.
+    Gone,
+    /// Alive, but the pid was reused by an unrelated process.
+    Reused,
+    /// Still our `proxy-daemon.js`.
+    Ours,
+}
+
+#[cfg(not(windows))]
+pub fn classify(pid: u32) -> Liveness {
+    if !is_alive(pid) {
+        Liveness::Gone
+    } else if !is_our_daemon(pid) {
+        Liveness::Reused
+    } else {
+        Liveness::Ours
+    }
+}
+
+/// Whether `pid` names a live process. `EPERM` means it exists but belongs
+/// to another user.
+#[cfg(not(windows))]
+pub fn is_alive(pid: u32) -> bool {
+    use nix::{errno::Errno, sys::signal::kill, unistd::Pid};
+
+    matches!(kill(Pid::from_raw(pid as i32), None), Ok(()) | Err(Errno::EPERM))
+}
+
+/// Whether `pid` is running our `desktop/proxy-daemon.js`, as opposed to an
+/// unrelated process that reused the pid.
+#[cfg(not(windows))]
+pub fn is_our_daemon(pid: u32) -> bool {
+    crate::port::process_command(pid)
+        .is_some_and(|command| command.contains("proxy-daemon.js"))
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use std::{
+        io::Read,
+        process::{Command, Stdio},
+    };
+
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pidfile-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn record() -> DaemonRecord {
+        DaemonRecord {
+            pid: 4242,
+            port: 8080,
+            started_at: "2024-01-02T03:04:05Z".to_string(),
+            shell_pid: 4141,
+        }
+    }
+
+    #[test]
+    fn the_lock_has_one_holder_at_a_time() {
+        let dir = scratch("lock");
+        let first = PidFile::acquire(&dir).unwrap();
+        let err = PidFile::acquire(&dir).err().unwrap();
+        assert!(err.contains("daemon.lock is held by another instance"), "{err}");
+        drop(first);
+        assert!(PidFile::acquire(&dir).is_ok());
+    }
+
+    #[test]
+    fn the_record_round_trips() {
+        let pidfile = PidFile::acquire(&scratch("record")).unwrap();
+        assert_eq!(pidfile.read(), Ok(None));
+        pidfile.write(&record()).unwrap();
+        assert_eq!(pidfile.read(), Ok(Some(record())));
+        let raw = fs::read_to_string(pidfile.path()).unwrap();
+        assert!(raw.contains("\"startedAt\""), "{raw}");
+        pidfile.clear();
+        assert_eq!(pidfile.read(), Ok(None));
+    }
+
+    #[test]
+    fn a_corrupt_record_is_reported() {
+        let pidfile = PidFile::acquire(&scratch("corrupt")).unwrap();
+        fs::write(pidfile.path(), "{\"pid\": ").unwrap();
+        let err = pidfile.read().unwrap_err();
+        assert!(err.starts_with("Ignoring invalid "), "{err}");
+        fs::write(pidfile.path(), r#"{"pid": 1}"#).unwrap();
+        assert!(pidfile.read().is_err(), "missing fields");
+    }
+
+    #[test]
+    fn a_recorded_pid_is_gone_reused_or_ours() {
+        let mut exited = Command::new("true").spawn().unwrap();
+        let gone = exited.id();
+        exited.wait().unwrap();
+        assert_eq!(classify(gone), Liveness::Gone);
+
+        // The test runner is alive but is not a daemon.
+        assert_eq!(classify(std::process::id()), Liveness::Reused);
+        assert!(is_alive(1), "init is alive even when it cannot be signalled");
+
+        // `$0` puts the daemon's script name on the command line. The echo
+        // shows `sh` has replaced the forked test runner.
+        let mut daemon = Command::new("sh")
+            .args(["-c", "echo; sleep 30; :", "proxy-daemon.js"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut ready = [0u8];
+        daemon.stdout.take().unwrap().read_exact(&mut ready).unwrap();
+        assert_eq!(classify(daemon.id()), Liveness::Ours);
+        daemon.kill().unwrap();
+        daemon.wait().unwrap();
+        assert_eq!(classify(daemon.id()), Liveness::Gone);
+    }
+}
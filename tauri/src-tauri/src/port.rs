@@ -0,0 +1,212 @@
+use std::net::TcpListener;
+
+use serde::Serialize;
+
+/// Process found listening on a port.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub command: Option<String>,
+}
+
+/// Why the shell could not use the port it wanted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortConflict {
+    pub port: u16,
+    pub owner: Option<PortOwner>,
+    /// First free port after `port`, if one was found.
+    pub suggested: Option<u16>,
+}
+
+impl PortConflict {
+    pub fn describe(&self) -> String {
+        let holder = match &self.owner {
+            Some(PortOwner {
+                pid,
+                command: Some(command),
+            }) => format!("pid {pid} ({command})"),
+            Some(PortOwner { pid, command: None }) => format!("pid {pid}"),
+            None => "another process".to_string(),
+        };
+        match self.suggested {
+            Some(free) => format!(
+                "Port {} is in use by {holder}; port {free} is free",
+                self.port
+            ),
+            None => format!("Port {} is in use by {holder}", self.port),
+        }
+    }
+}
+
+pub fn is_port_free(port: u16) -> bool {
+    TcpListener::bind(("127.0.0.1", port)).is_ok()
+}
+
+/// Scans up to `range` ports after `port` for one that can be bound.
+pub fn find_free_port(port: u16, range: u16) -> Option<u16> {
+    (1..=range)
+        .filter_map(|offset| port.checked_add(offset))
+        .find(|candidate| is_port_free(*candidate))
+}
+
+/// Identifies the process listening on `port` by matching the socket inode in
+/// `/proc/net/tcp{,6}` against `/proc/*/fd`. Sockets owned by other users are
+/// not visible without privileges, in which case this returns `None`.
+#[cfg(target_os = "linux")]
+pub fn port_owner(port: u16) -> Option<PortOwner> {
+    use std::fs;
+
+    let inodes: Vec<u64> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|table| fs::read_to_string(table).ok())
+        .flat_map(|contents| listening_inodes(&contents, port))
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+
+    let targets: Vec<String> = inodes
+        .iter()
+        .map(|inode| format!("socket:[{inode}]"))
+        .collect();
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let Ok(fds) = fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        let owns_socket = fds.flatten().any(|fd| {
+            fs::read_link(fd.path())
+                .map(|link| targets.iter().any(|target| link.as_os_str() == target.as_str()))
+                .unwrap_or(false)
+        });
+        if owns_socket {
+            return Some(PortOwner {
+                pid,
+                command: process_command(pid),
+            });
+        }
+    }
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn port_owner(_port: u16) -> Option<PortOwner> {
+    None
+}
+
+/// Command line of `pid` with arguments joined by spaces.
+#[cfg(target_os = "linux")]
+pub fn process_command(pid: u32) -> Option<String> {
+    let raw = std::fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    let command = raw
+        .split(|byte| *byte == 0)
+        .filter(|part| !part.is_empty())
+        .map(|part| String::from_utf8_lossy(part).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!command.is_empty()).then_some(command)
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn process_command(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "command=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let command = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !command.is_empty()).then_some(command)
+}
+
+#[cfg(windows)]
+pub fn process_command(_pid: u32) -> Option<String> {
+    None
+}
+
+/// Parses a `/proc/net/tcp`-style table and returns the inodes of sockets in
+/// LISTEN state (`0A`) bound to `port`.
+#[cfg(target_os = "linux")]
+fn listening_inodes(table: &str, port: u16) -> Vec<u64> {
+    const TCP_LISTEN: &str = "0A";
+
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let local = columns.get(1)?;
+            let state = columns.get(3)?;
+            let inode = columns.get(9)?.parse::<u64>().ok()?;
+            let (_, hex_port) = local.rsplit_once(':')?;
+            let local_port = u16::from_str_radix(hex_port, 16).ok()?;
+            (local_port == port && *state == TCP_LISTEN && inode != 0).then_some(inode)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn listening_inodes_match_port_and_state() {
+        // 0x1F90 is 8080; state 0A is LISTEN and 01 is ESTABLISHED.
+        let table = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:1F90 0100007F:D2C4 01 00000000:00000000 00:00000000 00000000  1000        0 41299 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:1F91 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41300 1 0000000000000000 100 0 0 10 0
+   3: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 0 1 0000000000000000 100 0 0 10 0
+   4: garbage
+";
+        assert_eq!(listening_inodes(table, 8080), [41234]);
+        assert_eq!(listening_inodes(table, 8081), [41300]);
+        assert!(listening_inodes(table, 9000).is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn listening_inodes_read_ipv6_tables() {
+        let table = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 52000 1 0000000000000000 100 0 0 10 0
+";
+        assert_eq!(listening_inodes(table, 8080), [52000]);
+    }
+
+    #[test]
+    fn conflicts_describe_the_holder() {
+        let conflict = PortConflict {
+            port: 8080,
+            owner: Some(PortOwner {
+                pid: 42,
+                command: Some("node server.js".to_string()),
+            }),
+            suggested: Some(8081),
+        };
+        assert_eq!(
+            conflict.describe(),
+            "Port 8080 is in use by pid 42 (node server.js); port 8081 is free"
+        );
+        let unknown = PortConflict {
+            owner: None,
+            suggested: None,
+            ..conflict
+        };
+        assert_eq!(unknown.describe(), "Port 8080 is in use by another process");
+    }
+
+    #[test]
+    fn a_bound_port_is_not_free() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(!is_port_free(port));
+        assert_eq!(find_free_port(port - 1, 1), None);
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+
+/// Version of the stdout event protocol this shell speaks. Bump it together with
+/// `PROTOCOL_VERSION` in `desktop/proxy-daemon.js` on breaking changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest daemon protocol the shell still understands.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Capabilities the shell cannot work without.
+const REQUIRED_CAPABILITIES: &[&str] = &["status"];
+/// Capabilities the shell uses when present; without them it runs degraded.
+const OPTIONAL_CAPABILITIES: &[&str] = &["heartbeat", "stop-ack", "control"];
+
+/// Contents of the daemon's `hello` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct Handshake {
+    pub protocol: u32,
+    pub capabilities: Vec<String>,
+    pub backend_version: Option<String>,
+    pub node_version: Option<String>,
+    pub pid: Option<u32>,
+}
+
+impl Handshake {
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+/// Outcome of validating the daemon against this shell.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", content = "reason", rename_all = "snake_case")]
+pub enum ProtocolState {
+    /// No daemon, or it has not said hello yet.
+    #[default]
+    Pending,
+    Compatible,
+    /// Usable, but some features are unavailable.
+    Degraded(String),
+    /// The shell refuses to drive this daemon.
+    Incompatible(String),
+}
+
+pub fn evaluate(hello: &Handshake) -> ProtocolState {
+    if hello.protocol > PROTOCOL_VERSION {
+        return ProtocolState::Incompatible(format!(
+            "Bundled backend speaks protocol v{} but this app only supports up to v{PROTOCOL_VERSION}; update the desktop app",
+            hello.protocol
+        ));
+    }
+    if hello.protocol < MIN_PROTOCOL_VERSION {
+        return ProtocolState::Incompatible(format!(
+            "Bundled backend speaks protocol v{} but this app requires at least v{MIN_PROTOCOL_VERSION}; re-sync the backend",
+            hello.protocol
+        ));
+    }
+
+    let missing_required: Vec<&str> = REQUIRED_CAPABILITIES
+        .iter()
+        .copied()
+        .filter(|capability| !hello.supports(capability))
+        .collect();
+    if !missing_required.is_empty() {
+        return ProtocolState::Incompatible(format!(
+            "Backend lacks required capabilities: {}",
+            missing_required.join(", ")
+        ));
+    }
+
+    let missing_optional: Vec<&str> = OPTIONAL_CAPABILITIES
+        .iter()
+        .copied()
+        .filter(|capability| !hello.supports(capability))
+        .collect();
+    if !missing_optional.is_empty() {
+        return ProtocolState::Degraded(format!(
+            "Backend lacks optional capabilities: {}",
+            missing_optional.join(", ")
+        ));
+    }
+
+    ProtocolState::Compatible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello(protocol: u32, capabilities: &[&str]) -> Handshake {
+        Handshake {
+            protocol,
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            backend_version: None,
+            node_version: None,
+            pid: None,
+        }
+    }
+
+    #[test]
+    fn current_protocol_with_everything_is_compatible() {
+        let hello = hello(PROTOCOL_VERSION, &["status", "heartbeat", "stop-ack", "control"]);
+        assert_eq!(evaluate(&hello), ProtocolState::Compatible);
+    }
+
+    #[test]
+    fn versions_outside_the_range_are_incompatible() {
+        let reason = |state| match state {
+            ProtocolState::Incompatible(reason) => reason,
+            other => panic!("expected incompatible, got {other:?}"),
+        };
+        let newer = reason(evaluate(&hello(PROTOCOL_VERSION + 1, &["status"])));
+        assert!(newer.ends_with("update the desktop app"), "{newer}");
+        let older = reason(evaluate(&hello(MIN_PROTOCOL_VERSION - 1, &["status"])));
+        assert!(older.ends_with("re-sync the backend"), "{older}");
+    }
+
+    #[test]
+    fn missing_capabilities() {
+        assert_eq!(
+            evaluate(&hello(PROTOCOL_VERSION, &["heartbeat"])),
+            ProtocolState::Incompatible("Backend lacks required capabilities: status".to_string())
+        );
+        assert_eq!(
+            evaluate(&hello(PROTOCOL_VERSION, &["status", "heartbeat"])),
+            ProtocolState::Degraded("Backend lacks optional capabilities: stop-ack, control".to_string())
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use std::borrow::Cow;
+
+use regex::{Captures, Regex};
+
+use crate::{patterns, settings::RedactSettings};
+
+const MASK: &str = "[REDACTED]";
+
+/// Values of these keys are masked wherever they appear as `"key": "value"`
+/// or `key=value`, whatever the value looks like.
+const SECRET_KEYS: &str = r"access_?token|refresh_?token|id_?token|client_?secret|admin_?api_?key|api_?key|password|authorization";
+
+/// Masks credentials in lines captured from the daemon before they reach
+/// `desktop.log` or the webview.
+pub struct Redactor {
+    enabled: bool,
+    /// Patterns whose whole match is replaced by `[REDACTED]`.
+    patterns: Vec<Regex>,
+    keyed: Regex,
+    email: Option<Regex>,
+}
+
+impl Redactor {
+    /// Builds the built-in patterns plus the user's own, returning messages
+    /// for any of the latter that were skipped.
+    pub fn new(settings: &RedactSettings) -> (Self, Vec<String>) {
+        let builtin = [
+            // `Authorization: Bearer ...`
+            r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]+=*",
+            // Google OAuth access tokens.
+            r"\bya29\.[0-9A-Za-z\-_]+",
+            // Google OAuth refresh tokens.
+            r"\b1//[0-9A-Za-z\-_]{20,}",
+            // Hex API keys such as the proxy's generated `adminApiKey`.
+            r"\b[0-9a-fA-F]{32,}\b",
+        ];
+        let mut patterns: Vec<Regex> = builtin
+            .iter()
+            .map(|pattern| Regex::new(pattern).expect("built-in redaction pattern"))
+            .collect();
+
+        let mut errors = Vec::new();
+        patterns.extend(patterns::compile_user(&settings.patterns, "redaction", &mut errors));
+
+        let keyed = Regex::new(&format!(
+            r#"(?i)("(?:{SECRET_KEYS})"\s*:\s*")[^"]*(")|\b((?:{SECRET_KEYS})\s*[=:]\s*)[^\s,;&"']+()"#
+        ))
+        .expect("built-in redaction pattern");
+        let email = settings
+            .emails
+            .then(|| Regex::new(r"\b([A-Za-z0-9])[A-Za-z0-9._%+\-]*@([A-Za-z0-9.\-]+\.[A-Za-z]{2,})\b"))
+            .transpose()
+            .expect("built-in redaction pattern");
+
+        let redactor = Self {
+            enabled: settings.enabled,
+            patterns,
+            keyed,
+            email,
+        };
+        (redactor, errors)
+    }
+
+    pub fn redact<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if !self.enabled {
+            return Cow::Borrowed(line);
+        }
+        let mut text = Cow::Borrowed(line);
+        for pattern in &self.patterns {
+            if let Cow::Owned(replaced) = pattern.replace_all(&text, MASK) {
+                text = Cow::Owned(replaced);
+            }
+        }
+        // Whatever is left under a secret-looking key; the key stays for context.
+        let keyed = self.keyed.replace_all(&text, |caps: &Captures| {
+            let (prefix, suffix) = match caps.get(1) {
+                Some(prefix) => (prefix.as_str(), caps.get(2).map_or("", |m| m.as_str())),
+                None => (caps.get(3).map_or("", |m| m.as_str()), ""),
+            };
+            format!("{prefix}{MASK}{suffix}")
+        });
+        if let Cow::Owned(replaced) = keyed {
+            text = Cow::Owned(replaced);
+        }
+        if let Some(email) = &self.email {
+            if let Cow::Owned(replaced) = email.replace_all(&text, "$1***@$2") {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor() -> Redactor {
+        Redactor::new(&RedactSettings::default()).0
+    }
+
+    #[test]
+    fn masks_tokens_and_keys() {
+        let redactor = redactor();
+        let cases = [
+            ("Authorization: Bearer abc.def-123==", "Authorization: [REDACTED]"),
+            ("token ya29.a0AfH6SMB_x-y", "token [REDACTED]"),
+            ("refresh 1//0gAbCdEfGhIjKlMnOpQrStUv", "refresh [REDACTED]"),
+            ("key 0123456789abcdef0123456789abcdef", "key [REDACTED]"),
+            (r#"{"access_token": "short", "x": 1}"#, r#"{"access_token": "[REDACTED]", "x": 1}"#),
+            ("url?apiKey=s3cr3t&x=1", "url?apiKey=[REDACTED]&x=1"),
+            ("password: hunter2, next", "password: [REDACTED], next"),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(redactor.redact(line), expected, "{line}");
+        }
+    }
+
+    #[test]
+    fn masks_email_local_parts() {
+        assert_eq!(
+            redactor().redact("switched to jane.doe@example.com"),
+            "switched to j***@example.com"
+        );
+        let keep = Redactor::new(&RedactSettings {
+            emails: false,
+            ..RedactSettings::default()
+        })
+        .0;
+        assert_eq!(keep.redact("jane@example.com"), "jane@example.com");
+    }
+
+    #[test]
+    fn clean_lines_are_borrowed() {
+        assert!(matches!(redactor().redact("Proxy started on 8080"), Cow::Borrowed(_)));
+        let disabled = Redactor::new(&RedactSettings {
+            enabled: false,
+            ..RedactSettings::default()
+        })
+        .0;
+        assert_eq!(disabled.redact("Bearer abc"), "Bearer abc");
+    }
+
+    #[test]
+    fn user_patterns_are_added_and_bad_ones_reported() {
+        let (redactor, errors) = Redactor::new(&RedactSettings {
+            patterns: vec![r"proj-\d+".to_string(), "(".to_string()],
+            ..RedactSettings::default()
+        });
+        assert_eq!(redactor.redact("project proj-42"), "project [REDACTED]");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with(r#"Ignoring redaction pattern "(""#), "{}", errors[0]);
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use dirs::home_dir;
+use serde::{Deserialize, Serialize};
+
+/// Directory that holds the desktop shell's own state (logs, settings).
+pub fn state_dir() -> PathBuf {
+    home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".antigravity-proxy")
+}
+
+pub fn settings_path() -> PathBuf {
+    state_dir().join("desktop-settings.json")
+}
+
+/// Settings for the desktop shell, read once at launch from
+/// `~/.antigravity-proxy/desktop-settings.json`. Every field is optional in
+/// the file; anything missing falls back to the defaults below.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DesktopSettings {
+    pub restart: RestartSettings,
+    pub stop: StopSettings,
+    pub startup: StartupSettings,
+    pub control: ControlSettings,
+    pub port: PortSettings,
+    pub log: LogSettings,
+    pub redact: RedactSettings,
+    pub stderr: StderrSettings,
+    pub notify: NotifySettings,
+    pub claude: ClaudeSettings,
+}
+
+/// Supervisor policy applied when the proxy daemon exits without being asked to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RestartSettings {
+    pub enabled: bool,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub multiplier: f64,
+    /// Number of exits inside `crash_loop_window_secs` that counts as a crash loop.
+    pub crash_loop_threshold: usize,
+    pub crash_loop_window_secs: u64,
+}
+
+impl Default for RestartSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+            multiplier: 2.0,
+            crash_loop_threshold: 5,
+            crash_loop_window_secs: 60,
+        }
+    }
+}
+
+/// Deadlines for the stop sequence: SIGTERM, wait for the daemon to report
+/// `stopping`, wait for it to exit, then SIGKILL its process group.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StopSettings {
+    /// How long the daemon has to emit `stopping`/`stopped` after SIGTERM.
+    pub ack_timeout_ms: u64,
+    /// How long an acknowledged daemon has to finish `stopProxy()` and exit.
+    pub grace_timeout_ms: u64,
+    /// How long to wait for the process to disappear after SIGKILL.
+    pub kill_timeout_ms: u64,
+}
+
+impl Default for StopSettings {
+    fn default() -> Self {
+        Self {
+            ack_timeout_ms: 3_000,
+            grace_timeout_ms: 10_000,
+            kill_timeout_ms: 5_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StartupSettings {
+    /// How long the daemon has to send its `hello` before it is treated as legacy.
+    pub handshake_timeout_ms: u64,
+    /// How long `start_proxy` waits for `started` and a healthy `/health`.
+    pub ready_timeout_ms: u64,
+    pub health_poll_interval_ms: u64,
+    /// What to do at launch with a daemon left running by a previous session.
+    /// Ignored on Windows, which has no orphan recovery.
+    pub orphan_policy: OrphanPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanPolicy {
+    /// Keep it running and monitor it through `/health`.
+    #[default]
+    Adopt,
+    Terminate,
+}
+
+impl Default for StartupSettings {
+    fn default() -> Self {
+        Self {
+            handshake_timeout_ms: 5_000,
+            ready_timeout_ms: 20_000,
+            health_poll_interval_ms: 500,
+            orphan_policy: OrphanPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ControlSettings {
+    /// How long a stdin command may wait for the daemon's `response` event.
+    pub timeout_ms: u64,
+}
+
+impl Default for ControlSettings {
+    fn default() -> Self {
+        Self { timeout_ms: 10_000 }
+    }
+}
+
+/// Which port the daemon listens on and what to do when it is taken.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PortSettings {
+    pub preferred: u16,
+    /// Move to the next free port instead of failing when `preferred` is taken.
+    pub auto_select: bool,
+    /// How many ports after `preferred` to try.
+    pub search_range: u16,
+}
+
+impl Default for PortSettings {
+    fn default() -> Self {
+        Self {
+            preferred: 8080,
+            auto_select: true,
+            search_range: 20,
+        }
+    }
+}
+
+/// Rotation of `desktop.log`. Rotated files sit next to it as
+/// `desktop.<timestamp>.log`, or `.log.gz` when `compress` is on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LogSettings {
+    /// Rotate once the file would grow past this size; 0 disables size rotation.
+    pub max_bytes: u64,
+    /// Rotate when the UTC day changes.
+    pub daily: bool,
+    /// How many rotated files to keep.
+    pub retain: usize,
+    pub compress: bool,
+    pub format: LogFormat,
+}
+
+/// Line format of `desktop.log`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// `[timestamp] [LEVEL] line`.
+    #[default]
+    Text,
+    /// One JSON record per line; daemon events keep their parsed payload.
+    Json,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024 * 1024,
+            daily: true,
+            retain: 5,
+            compress: false,
+            format: LogFormat::Text,
+        }
+    }
+}
+
+/// Masking of credentials in daemon output before it is logged or shown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RedactSettings {
+    pub enabled: bool,
+    /// Mask the local part of email addresses (`j***@example.com`).
+    pub emails: bool,
+    /// Extra regular expressions whose matches are replaced by `[REDACTED]`.
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            emails: true,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// How daemon stderr is sorted into warnings and errors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StderrSettings {
+    /// Lines arriving within this long of each other form one report, so a
+    /// stack trace counts once.
+    pub aggregation_window_ms: u64,
+    /// Distinct warnings kept for the status view.
+    pub max_warnings: usize,
+    /// Regular expressions for lines that are always warnings.
+    pub warning_patterns: Vec<String>,
+    /// Regular expressions for lines that are always errors; these win.
+    pub error_patterns: Vec<String>,
+}
+
+impl Default for StderrSettings {
+    fn default() -> Self {
+        Self {
+            aggregation_window_ms: 250,
+            max_warnings: 20,
+            warning_patterns: Vec::new(),
+            error_patterns: Vec::new(),
+        }
+    }
+}
+
+/// Desktop notifications, per category.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NotifySettings {
+    pub enabled: bool,
+    /// The daemon exited without being asked to.
+    pub crash: bool,
+    /// Every usable account is rate limited.
+    pub rate_limited: bool,
+    /// An account was marked invalid.
+    pub invalid_account: bool,
+    /// `~/.claude/settings.json` no longer points at the proxy.
+    pub config_drift: bool,
+    /// The same notification is not repeated within this many seconds.
+    pub cooldown_secs: u64,
+}
+
+impl Default for NotifySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            crash: true,
+            rate_limited: true,
+            invalid_account: true,
+            config_drift: true,
+            cooldown_secs: 600,
+        }
+    }
+}
+
+/// How the shell manages `~/.claude/settings.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ClaudeSettings {
+    /// Merge the proxy env into `settings.json` whenever the proxy is started
+    /// from the app, as the daemon used to do on its own.
+    pub attach_on_start: bool,
+    /// Remove the proxy env when the app quits and put it back on the next
+    /// launch, so Claude works without the proxy in between.
+    pub detach_on_quit: bool,
+}
+
+impl Default for ClaudeSettings {
+    fn default() -> Self {
+        Self {
+            attach_on_start: true,
+            detach_on_quit: false,
+        }
+    }
+}
+
+impl DesktopSettings {
+    /// Loads settings from `path`. A missing file yields the defaults; an
+    /// unreadable or malformed file yields the defaults plus the reason, so the
+    /// caller can surface it instead of silently ignoring the user's edits.
+    pub fn load(path: &Path) -> (Self, Option<String>) {
+        let raw = match fs::read_to_string(path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return (Self::default(), None)
+            }
+            Err(err) => {
+                return (
+                    Self::default(),
+                    Some(format!("Unable to read {}: {err}", path.display())),
+                )
+            }
+        };
+
+        match serde_json::from_str::<Self>(&raw) {
+            Ok(settings) => (settings, None),
+            Err(err) => (
+                Self::default(),
+                Some(format!("Ignoring invalid {}: {err}", path.display())),
+            ),
+        }
+    }
+}
@@ -0,0 +1,434 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Typed view of the `getStatus()` payload from `src/index.js`.
+///
+/// Parsing never fails: each field is read on its own, and anything missing,
+/// mistyped or unexpected is reported as a schema warning so protocol drift
+/// between the shell and the bundled backend shows up in the log.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonSnapshot {
+    pub running: bool,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub started_at: Option<String>,
+    pub lan_enabled: bool,
+    pub current_account: Option<String>,
+    pub account_summary: Option<String>,
+    pub accounts: Vec<AccountSnapshot>,
+    pub recommended_account: Option<String>,
+    pub claude_config: Option<ClaudeConfigSnapshot>,
+    pub initialized: bool,
+    pub init_error: Option<String>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountSnapshot {
+    pub email: String,
+    pub source: Option<String>,
+    pub is_rate_limited: bool,
+    pub rate_limit_reset_time: Option<i64>,
+    pub is_invalid: bool,
+    pub invalid_reason: Option<String>,
+    pub last_used: Option<i64>,
+    pub health_score: f64,
+    pub recommended: bool,
+    pub next_available_at: Option<i64>,
+    pub stats: Option<Value>,
+}
+
+/// The daemon's own view of `~/.claude/settings.json` (`describeClaudeConfigStatus`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaudeConfigSnapshot {
+    pub healthy: bool,
+    pub settings_path: Option<String>,
+    pub expected_env: Option<Value>,
+    pub current: Option<Value>,
+    pub error: Option<String>,
+}
+
+impl DaemonSnapshot {
+    /// Parses a snapshot, returning it together with any schema warnings.
+    pub fn from_value(value: &Value) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let Some(object) = value.as_object() else {
+            warnings.push(format!("snapshot: expected object, got {}", kind(value)));
+            return (Self::default(), warnings);
+        };
+
+        let mut fields = Fields::new(object, "snapshot", &mut warnings);
+        let snapshot = Self {
+            running: fields.boolean("running"),
+            host: fields.string("host"),
+            port: fields.port("port"),
+            started_at: fields.string("startedAt"),
+            lan_enabled: fields.boolean("lanEnabled"),
+            current_account: fields.string("currentAccount"),
+            account_summary: fields.string("accountSummary"),
+            accounts: fields.list("accounts", AccountSnapshot::from_value),
+            recommended_account: fields.string("recommendedAccount"),
+            claude_config: fields.nested("claudeConfig", ClaudeConfigSnapshot::from_value),
+            initialized: fields.boolean("initialized"),
+            init_error: fields.string("initError"),
+            last_error: fields.string("lastError"),
+        };
+        fields.finish();
+        (snapshot, warnings)
+    }
+
+    pub fn has_rate_limited_account(&self) -> bool {
+        self.accounts.iter().any(|account| account.is_rate_limited)
+    }
+
+    pub fn rate_limited_count(&self) -> u32 {
+        self.accounts.iter().filter(|account| account.is_rate_limited).count() as u32
+    }
+
+    /// Milliseconds until the earliest rate-limited account frees up, if any.
+    pub fn shortest_wait_ms(&self, now_ms: i64) -> Option<i64> {
+        self.accounts
+            .iter()
+            .filter_map(|account| account.next_available_at)
+            .map(|ts| ts - now_ms)
+            .filter(|delta| *delta > 0)
+            .min()
+    }
+
+    /// Accounts whose `nextAvailableAt` is still ahead, with the milliseconds
+    /// left, soonest first.
+    pub fn cooling_down(&self, now_ms: i64) -> Vec<(&AccountSnapshot, i64)> {
+        let mut waits: Vec<(&AccountSnapshot, i64)> = self
+            .accounts
+            .iter()
+            .filter_map(|account| Some((account, account.next_available_at? - now_ms)))
+            .filter(|(_, wait)| *wait > 0)
+            .collect();
+        waits.sort_by_key(|(_, wait)| *wait);
+        waits
+    }
+}
+
+impl AccountSnapshot {
+    fn from_value(value: &Value, context: &str, warnings: &mut Vec<String>) -> Option<Self> {
+        let Some(object) = value.as_object() else {
+            warnings.push(format!("{context}: expected object, got {}", kind(value)));
+            return None;
+        };
+
+        let mut fields = Fields::new(object, context, warnings);
+        let account = Self {
+            email: fields.string("email").unwrap_or_default(),
+            source: fields.optional_string("source"),
+            is_rate_limited: fields.boolean("isRateLimited"),
+            rate_limit_reset_time: fields.optional_integer("rateLimitResetTime"),
+            is_invalid: fields.boolean("isInvalid"),
+            invalid_reason: fields.optional_string("invalidReason"),
+            last_used: fields.optional_integer("lastUsed"),
+            health_score: fields.number("healthScore"),
+            recommended: fields.optional_boolean("recommended"),
+            next_available_at: fields.optional_integer("nextAvailableAt"),
+            stats: fields.raw("stats"),
+        };
+        fields.finish();
+        Some(account)
+    }
+}
+
+impl ClaudeConfigSnapshot {
+    fn from_value(value: &Value, context: &str, warnings: &mut Vec<String>) -> Option<Self> {
+        let Some(object) = value.as_object() else {
+            warnings.push(format!("{context}: expected object, got {}", kind(value)));
+            return None;
+        };
+
+        // `error` is only present when the daemon failed to read the settings.
+        let mut fields = Fields::new(object, context, warnings);
+        let config = Self {
+            healthy: fields.boolean("healthy"),
+            settings_path: fields.optional_string("settingsPath"),
+            expected_env: fields.raw("expectedEnv"),
+            current: fields.raw("current"),
+            error: fields.optional_string("error"),
+        };
+        fields.finish();
+        Some(config)
+    }
+}
+
+/// Reads fields out of a JSON object one at a time, recording mismatches
+/// instead of failing. Plain accessors warn when the key is absent; the
+/// `optional_*` ones accept absence silently. `null` is always accepted.
+struct Fields<'a> {
+    object: &'a Map<String, Value>,
+    context: &'a str,
+    seen: Vec<&'static str>,
+    warnings: &'a mut Vec<String>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(object: &'a Map<String, Value>, context: &'a str, warnings: &'a mut Vec<String>) -> Self {
+        Self {
+            object,
+            context,
+            seen: Vec::new(),
+            warnings,
+        }
+    }
+
+    fn lookup(&mut self, key: &'static str, required: bool) -> Option<&'a Value> {
+        self.seen.push(key);
+        match self.object.get(key) {
+            None => {
+                if required {
+                    self.warnings
+                        .push(format!("{}.{key}: missing field", self.context));
+                }
+                None
+            }
+            Some(Value::Null) => None,
+            Some(value) => Some(value),
+        }
+    }
+
+    fn mismatch(&mut self, key: &str, expected: &str, value: &Value) {
+        self.warnings.push(format!(
+            "{}.{key}: expected {expected}, got {}",
+            self.context,
+            kind(value)
+        ));
+    }
+
+    fn read_string(&mut self, key: &'static str, required: bool) -> Option<String> {
+        let value = self.lookup(key, required)?;
+        match value {
+            Value::String(text) => Some(text.clone()),
+            other => {
+                self.mismatch(key, "string", other);
+                None
+            }
+        }
+    }
+
+    fn string(&mut self, key: &'static str) -> Option<String> {
+        self.read_string(key, true)
+    }
+
+    fn optional_string(&mut self, key: &'static str) -> Option<String> {
+        self.read_string(key, false)
+    }
+
+    fn read_boolean(&mut self, key: &'static str, required: bool) -> bool {
+        match self.lookup(key, required) {
+            None => false,
+            Some(Value::Bool(flag)) => *flag,
+            Some(other) => {
+                self.mismatch(key, "boolean", other);
+                false
+            }
+        }
+    }
+
+    fn boolean(&mut self, key: &'static str) -> bool {
+        self.read_boolean(key, true)
+    }
+
+    fn optional_boolean(&mut self, key: &'static str) -> bool {
+        self.read_boolean(key, false)
+    }
+
+    fn number(&mut self, key: &'static str) -> f64 {
+        match self.lookup(key, true) {
+            None => 0.0,
+            Some(Value::Number(number)) => number.as_f64().unwrap_or_default(),
+            Some(other) => {
+                self.mismatch(key, "number", other);
+                0.0
+            }
+        }
+    }
+
+    /// Integers may arrive as floats (arithmetic on `Date.now()`) or numeric strings.
+    fn optional_integer(&mut self, key: &'static str) -> Option<i64> {
+        let value = self.lookup(key, false)?;
+        let parsed = match value {
+            Value::Number(number) => number
+                .as_i64()
+                .or_else(|| number.as_f64().map(|f| f as i64)),
+            Value::String(text) => text.trim().parse::<i64>().ok(),
+            _ => None,
+        };
+        if parsed.is_none() {
+            self.mismatch(key, "integer", value);
+        }
+        parsed
+    }
+
+    /// The port comes from `process.env.PORT` on some paths, so accept strings too.
+    fn port(&mut self, key: &'static str) -> Option<u16> {
+        let value = self.lookup(key, true)?;
+        let parsed = match value {
+            Value::Number(number) => number.as_u64().and_then(|n| u16::try_from(n).ok()),
+            Value::String(text) => text.trim().parse::<u16>().ok(),
+            _ => None,
+        };
+        if parsed.is_none() {
+            self.mismatch(key, "port number", value);
+        }
+        parsed
+    }
+
+    fn raw(&mut self, key: &'static str) -> Option<Value> {
+        self.lookup(key, false).cloned()
+    }
+
+    fn list<T>(
+        &mut self,
+        key: &'static str,
+        parse: fn(&Value, &str, &mut Vec<String>) -> Option<T>,
+    ) -> Vec<T> {
+        match self.lookup(key, true) {
+            None => Vec::new(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .enumerate()
+                .filter_map(|(index, item)| {
+                    let context = format!("{}.{key}[{index}]", self.context);
+                    parse(item, &context, self.warnings)
+                })
+                .collect(),
+            Some(other) => {
+                self.mismatch(key, "array", other);
+                Vec::new()
+            }
+        }
+    }
+
+    fn nested<T>(
+        &mut self,
+        key: &'static str,
+        parse: fn(&Value, &str, &mut Vec<String>) -> Option<T>,
+    ) -> Option<T> {
+        let value = self.lookup(key, true)?;
+        let context = format!("{}.{key}", self.context);
+        parse(value, &context, self.warnings)
+    }
+
+    /// Reports keys the shell does not know about.
+    fn finish(self) {
+        for key in self.object.keys() {
+            if !self.seen.contains(&key.as_str()) {
+                self.warnings
+                    .push(format!("{}.{key}: unexpected field", self.context));
+            }
+        }
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn full() -> Value {
+        json!({
+            "running": true,
+            "host": "127.0.0.1",
+            "port": 8080,
+            "startedAt": "2024-01-02T03:04:05Z",
+            "lanEnabled": false,
+            "currentAccount": "a@x",
+            "accountSummary": "1 account",
+            "accounts": [{
+                "email": "a@x",
+                "isRateLimited": true,
+                "isInvalid": false,
+                "healthScore": 0.5,
+                "nextAvailableAt": 1_700_000_060_000_i64,
+            }],
+            "recommendedAccount": null,
+            "claudeConfig": { "healthy": true, "settingsPath": "/home/a/.claude/settings.json" },
+            "initialized": true,
+            "initError": null,
+            "lastError": null,
+        })
+    }
+
+    #[test]
+    fn well_formed_snapshot_has_no_warnings() {
+        let (snapshot, warnings) = DaemonSnapshot::from_value(&full());
+        assert_eq!(warnings, Vec::<String>::new());
+        assert_eq!(snapshot.port, Some(8080));
+        assert_eq!(snapshot.accounts[0].next_available_at, Some(1_700_000_060_000));
+        assert!(snapshot.claude_config.unwrap().healthy);
+    }
+
+    #[test]
+    fn not_an_object() {
+        let (snapshot, warnings) = DaemonSnapshot::from_value(&json!([1]));
+        assert_eq!(snapshot, DaemonSnapshot::default());
+        assert_eq!(warnings, ["snapshot: expected object, got array"]);
+    }
+
+    #[test]
+    fn drift_is_reported_field_by_field() {
+        let mut value = full();
+        let object = value.as_object_mut().unwrap();
+        object.remove("host");
+        object.insert("running".into(), json!("yes"));
+        object.insert("port".into(), json!("8081"));
+        object.insert("extra".into(), json!(1));
+        let account = json!({
+            "email": "b@x",
+            "isRateLimited": false,
+            "isInvalid": false,
+            "healthScore": 1,
+            "nextAvailableAt": 1.5e12,
+            "lastUsed": "soon",
+        });
+        object.insert("accounts".into(), json!(["nope", account]));
+        let (snapshot, warnings) = DaemonSnapshot::from_value(&value);
+
+        assert!(!snapshot.running);
+        assert_eq!(snapshot.host, None);
+        // Ports arrive as strings from `process.env.PORT`.
+        assert_eq!(snapshot.port, Some(8081));
+        assert_eq!(snapshot.accounts.len(), 1);
+        assert_eq!(snapshot.accounts[0].next_available_at, Some(1_500_000_000_000));
+        assert_eq!(
+            warnings,
+            [
+                "snapshot.running: expected boolean, got string",
+                "snapshot.host: missing field",
+                "snapshot.accounts[0]: expected object, got string",
+                "snapshot.accounts[1].lastUsed: expected integer, got string",
+                "snapshot.extra: unexpected field",
+            ]
+        );
+    }
+
+    #[test]
+    fn waits_count_from_now() {
+        let (snapshot, _) = DaemonSnapshot::from_value(&full());
+        let now = 1_700_000_000_000;
+        assert_eq!(snapshot.shortest_wait_ms(now), Some(60_000));
+        assert_eq!(snapshot.cooling_down(now)[0].1, 60_000);
+        assert!(snapshot.cooling_down(now + 60_000).is_empty());
+        assert_eq!(snapshot.rate_limited_count(), 1);
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::VecDeque;
+
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::{patterns, settings::StderrSettings};
+
+/// Output Node and the proxy write to stderr that is not a failure.
+const KNOWN_WARNINGS: &[&str] = &[
+    r"\b(?:Deprecation|Experimental)Warning\b",
+    r"^\(node:\d+\) ",
+    r"^\(Use `node --trace-",
+    r"^\[Startup\] (?:Account manager initialization warning|Could not pre-initialize)",
+    r"^\[FlowMonitor\] ",
+    r"^\[ConfigService\] Failed to persist",
+    r"^\[[^\]]+\] .*\bwarning\b",
+    r"^warn(?:ing)?\b",
+];
+
+/// Lines that mean the proxy (or a request through it) actually failed.
+const KNOWN_ERRORS: &[&str] = &[
+    r"^\s*(?:Uncaught|Unhandled)",
+    r"^(?:\w+)?Error\b(?::|\s*\[)",
+    r"^\[(?:Proxy|Server)\] Failed to (?:start|initialize)",
+    r"^\[(?:API|OpenAI)\] (?:Stream )?[Ee]rror:",
+    r"\bE(?:ADDRINUSE|ACCES|ADDRNOTAVAIL)\b",
+    r"\b(?:FATAL|[Ff]atal error)\b",
+    r"\bout of memory\b",
+];
+
+/// Stack frames and source excerpts that follow an error and say nothing on
+/// their own.
+const CONTINUATION: &str = r"^(?:\s+at\s|\s*\^+\s*$|\s*$|\s{2,}\S)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Noise,
+    Warning,
+    Error,
+}
+
+/// Sorts daemon stderr into warnings and genuine failures. The user's own
+/// patterns win over the built-in lists, and known errors win over known
+/// warnings. The error patterns are anchored, so a warning that quotes an
+/// `Error:` stays a warning. Anything unrecognised is a warning.
+pub struct StderrClassifier {
+    user_errors: Vec<Regex>,
+    user_warnings: Vec<Regex>,
+    warnings: Vec<Regex>,
+    errors: Vec<Regex>,
+    continuation: Regex,
+}
+
+impl StderrClassifier {
+    /// Returns messages for any of the user's patterns that were skipped.
+    pub fn new(settings: &StderrSettings) -> (Self, Vec<String>) {
+        let mut messages = Vec::new();
+        let user_errors = patterns::compile_user(&settings.error_patterns, "stderr", &mut messages);
+        let user_warnings = patterns::compile_user(&settings.warning_patterns, "stderr", &mut messages);
+        let builtin = |patterns: &[&str], case_insensitive: bool| -> Vec<Regex> {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    RegexBuilder::new(pattern)
+                        .case_insensitive(case_insensitive)
+                        .build()
+                        .expect("built-in stderr pattern")
+                })
+                .collect()
+        };
+
+        let classifier = Self {
+            user_errors,
+            user_warnings,
+            warnings: builtin(KNOWN_WARNINGS, true),
+            errors: builtin(KNOWN_ERRORS, false),
+            continuation: Regex::new(CONTINUATION).expect("built-in stderr pattern"),
+        };
+        (classifier, messages)
+    }
+
+    pub fn classify_line(&self, line: &str) -> Severity {
+        let any = |patterns: &[Regex]| patterns.iter().any(|pattern| pattern.is_match(line));
+        if any(&self.user_errors) {
+            Severity::Error
+        } else if any(&self.user_warnings) {
+            Severity::Warning
+        } else if any(&self.errors) {
+            Severity::Error
+        } else if any(&self.warnings) {
+            Severity::Warning
+        } else if self.continuation.is_match(line) {
+            Severity::Noise
+        } else {
+            Severity::Warning
+        }
+    }
+
+    /// Classifies a burst of lines as one report: the most severe line decides,
+    /// and that line becomes the message.
+    pub fn classify(&self, lines: &[String]) -> Option<StderrReport> {
+        let (severity, headline) = lines
+            .iter()
+            .map(|line| (self.classify_line(line), line))
+            .filter(|(severity, _)| *severity != Severity::Noise)
+            // `max_by_key` keeps the last maximum; prefer the first.
+            .rev()
+            .max_by_key(|(severity, _)| *severity)?;
+        let extra = lines.len() - 1;
+        let message = if extra == 0 {
+            headline.trim().to_string()
+        } else {
+            format!("{} (+{extra} more lines)", headline.trim())
+        };
+        Some(StderrReport { severity, message })
+    }
+}
+
+pub struct StderrReport {
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// A non-fatal stderr report, folded with earlier identical ones.
+#[derive(Debug, Clone, Serialize)]
+pub struct DaemonWarning {
+    pub message: String,
+    pub count: u32,
+    pub first_seen: String,
+    pub last_seen: String,
+}
+
+/// Most recent warnings first, at most `capacity` of them.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings {
+    entries: VecDeque<DaemonWarning>,
+}
+
+impl Warnings {
+    pub fn record(&mut self, message: String, now: String, capacity: usize) {
+        if let Some(index) = self.entries.iter().position(|entry| entry.message == message) {
+            let mut entry = self.entries.remove(index).expect("index from position");
+            entry.count += 1;
+            entry.last_seen = now;
+            self.entries.push_front(entry);
+            return;
+        }
+        self.entries.push_front(DaemonWarning {
+            message,
+            count: 1,
+            first_seen: now.clone(),
+            last_seen: now,
+        });
+        self.entries.truncate(capacity.max(1));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn to_vec(&self) -> Vec<DaemonWarning> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classifier() -> StderrClassifier {
+        StderrClassifier::new(&StderrSettings::default()).0
+    }
+
+    #[test]
+    fn classifies_known_lines() {
+        let classifier = classifier();
+        let cases = [
+            ("(node:1234) ExperimentalWarning: Fetch API", Severity::Warning),
+            ("[FlowMonitor] queue is slow", Severity::Warning),
+            ("Error: listen EADDRINUSE: address already in use :::8080", Severity::Error),
+            ("TypeError [ERR_INVALID_ARG_TYPE]: bad", Severity::Error),
+            ("[Proxy] Failed to start", Severity::Error),
+            ("Uncaught exception in handler", Severity::Error),
+            ("    at Server.listen (node:net:1:1)", Severity::Noise),
+            ("      ^^^", Severity::Noise),
+            ("something nobody anticipated", Severity::Warning),
+        ];
+        for (line, severity) in cases {
+            assert_eq!(classifier.classify_line(line), severity, "{line}");
+        }
+    }
+
+    #[test]
+    fn warnings_win_over_quoted_errors() {
+        let line = "(node:1) Warning: Error: something deprecated";
+        assert_eq!(classifier().classify_line(line), Severity::Warning);
+    }
+
+    #[test]
+    fn errors_that_mention_warnings_stay_errors() {
+        let classifier = classifier();
+        let cases = [
+            ("Error: cannot warn the client", Severity::Error),
+            ("[Proxy] Failed to start: warning threshold exceeded", Severity::Error),
+            ("FATAL: warning log is full", Severity::Error),
+            ("warn: upstream is slow", Severity::Warning),
+            ("Warning: no accounts configured", Severity::Warning),
+            ("[CloudCode] quota warning for project", Severity::Warning),
+            ("(node:42) MaxListenersExceededWarning: possible leak", Severity::Warning),
+        ];
+        for (line, severity) in cases {
+            assert_eq!(classifier.classify_line(line), severity, "{line}");
+        }
+    }
+
+    #[test]
+    fn user_patterns_take_precedence() {
+        let (classifier, messages) = StderrClassifier::new(&StderrSettings {
+            error_patterns: vec!["quota".to_string()],
+            warning_patterns: vec![r"^Error: benign".to_string(), "[".to_string()],
+            ..StderrSettings::default()
+        });
+        assert_eq!(classifier.classify_line("warning: quota exhausted"), Severity::Error);
+        assert_eq!(classifier.classify_line("Error: benign hiccup"), Severity::Warning);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with(r#"Ignoring stderr pattern "[""#), "{}", messages[0]);
+    }
+
+    #[test]
+    fn a_burst_reports_its_worst_line() {
+        let lines: Vec<String> = [
+            "(node:1) DeprecationWarning: old",
+            "Error: boom",
+            "    at main (index.js:1:1)",
+            "Error: second",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        let report = classifier().classify(&lines).unwrap();
+        assert_eq!(report.severity, Severity::Error);
+        assert_eq!(report.message, "Error: boom (+3 more lines)");
+
+        let noise = vec!["    at main (index.js:1:1)".to_string()];
+        assert!(classifier().classify(&noise).is_none());
+    }
+
+    #[test]
+    fn warnings_fold_repeats_and_keep_the_newest() {
+        let mut warnings = Warnings::default();
+        warnings.record("a".into(), "t1".into(), 2);
+        warnings.record("b".into(), "t2".into(), 2);
+        warnings.record("a".into(), "t3".into(), 2);
+        warnings.record("c".into(), "t4".into(), 2);
+        let kept: Vec<_> = warnings
+            .to_vec()
+            .into_iter()
+            .map(|warning| (warning.message, warning.count, warning.first_seen, warning.last_seen))
+            .collect();
+        assert_eq!(
+            kept,
+            [
+                ("c".to_string(), 1, "t4".to_string(), "t4".to_string()),
+                ("a".to_string(), 2, "t1".to_string(), "t3".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    collections::VecDeque,
+    process::ExitStatus,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+#[cfg(not(windows))]
+use tokio::{process::Child, sync::watch, time::timeout};
+
+use crate::settings::RestartSettings;
+#[cfg(not(windows))]
+use crate::settings::StopSettings;
+
+/// What the supervisor should do after the daemon exited on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestartDecision {
+    /// Automatic restarts are turned off in the settings.
+    Disabled,
+    /// Spawn the daemon again after `delay`; `attempt` counts exits inside the window.
+    Restart { attempt: u32, delay: Duration },
+    /// Too many exits inside the window; stop retrying until the user starts it again.
+    CrashLoop { exits: usize, window: Duration },
+}
+
+/// Tracks recent unexpected exits and turns them into restart decisions with
+/// exponential backoff. Exits older than the crash-loop window are forgotten, so
+/// a daemon that stays up for a while starts again from the initial backoff.
+pub struct RestartPolicy {
+    settings: RestartSettings,
+    exits: VecDeque<Instant>,
+}
+
+impl RestartPolicy {
+    pub fn new(settings: RestartSettings) -> Self {
+        Self {
+            settings,
+            exits: VecDeque::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.exits.clear();
+    }
+
+    pub fn record_exit(&mut self, now: Instant) -> RestartDecision {
+        if !self.settings.enabled {
+            return RestartDecision::Disabled;
+        }
+
+        let window = Duration::from_secs(self.settings.crash_loop_window_secs);
+        while let Some(oldest) = self.exits.front() {
+            if now.duration_since(*oldest) > window {
+                self.exits.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.exits.push_back(now);
+
+        let exits = self.exits.len();
+        if exits >= self.settings.crash_loop_threshold.max(1) {
+            return RestartDecision::CrashLoop { exits, window };
+        }
+
+        let attempt = exits as u32;
+        RestartDecision::Restart {
+            attempt,
+            delay: self.backoff(attempt),
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let scaled = self.settings.initial_backoff_ms as f64
+            * self.settings.multiplier.max(1.0).powi(exponent);
+        let capped = scaled.min(self.settings.max_backoff_ms as f64);
+        Duration::from_millis(capped as u64)
+    }
+}
+
+pub fn describe_exit(exit: &ExitStatus) -> String {
+    if let Some(code) = exit.code() {
+        return format!("Proxy process exited with code {code}");
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = exit.signal() {
+            return format!("Proxy process killed by signal {signal}");
+        }
+    }
+
+    "Proxy process exited".to_string()
+}
+
+/// How a stop request ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopOutcome {
+    /// There was no daemon to stop.
+    NotRunning,
+    /// The daemon shut down on SIGTERM within the deadlines.
+    Graceful,
+    /// The daemon missed a deadline and its process group was SIGKILLed.
+    Killed,
+    /// The daemon was still alive after SIGKILL and the kill deadline.
+    Unresponsive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StopReport {
+    pub outcome: StopOutcome,
+    /// Whether the daemon emitted `stopping`/`stopped` before the ack deadline.
+    pub acknowledged: bool,
+    pub exit_code: Option<i32>,
+    pub elapsed_ms: u64,
+}
+
+impl StopReport {
+    pub fn not_running() -> Self {
+        Self {
+            outcome: StopOutcome::NotRunning,
+            acknowledged: false,
+            exit_code: None,
+            elapsed_ms: 0,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self.outcome {
+            StopOutcome::NotRunning => "Proxy was not running".to_string(),
+            StopOutcome::Graceful => format!("Proxy stopped gracefully in {}ms", self.elapsed_ms),
+            StopOutcome::Killed if self.acknowledged => format!(
+                "Proxy did not exit after stopping; killed after {}ms",
+                self.elapsed_ms
+            ),
+            StopOutcome::Killed => format!(
+                "Proxy did not acknowledge SIGTERM; killed after {}ms",
+                self.elapsed_ms
+            ),
+            StopOutcome::Unresponsive => {
+                "Proxy did not exit after SIGKILL; it may still be running".to_string()
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn signal_process_group(pid: Option<u32>, signal: nix::sys::signal::Signal) {
+    use nix::sys::signal::killpg;
+    use nix::unistd::Pid;
+
+    if let Some(pid) = pid {
+        let _ = killpg(Pid::from_raw(pid as i32), signal);
+    }
+}
+
+/// Stops `child`, the leader of its own process group, without ever blocking
+/// indefinitely: SIGTERM the group, wait for `phase` to report
+/// `stopping`/`stopped`, give it the grace period to exit, and SIGKILL the
+/// whole group if any deadline passes.
+#[cfg(not(windows))]
+pub async fn stop_group(
+    child: &mut Child,
+    timeouts: &StopSettings,
+    mut phase: watch::Receiver<Option<String>>,
+) -> StopReport {
+    use nix::sys::signal::Signal;
+
+    let started = Instant::now();
+    let pid = child.id();
+    let current = phase.clone();
+
+    signal_process_group(pid, Signal::SIGTERM);
+
+    let mut exit = None;
+    let acknowledged = tokio::select! {
+        acked = timeout(
+            Duration::from_millis(timeouts.ack_timeout_ms),
+            phase.wait_for(|p| matches!(p.as_deref(), Some("stopping" | "stopped"))),
+        ) => matches!(acked, Ok(Ok(_))),
+        waited = child.wait() => {
+            exit = waited.ok();
+            matches!(current.borrow().as_deref(), Some("stopping" | "stopped"))
+        }
+    };
+
+    if exit.is_none() && acknowledged {
+        exit = timeout(Duration::from_millis(timeouts.grace_timeout_ms), child.wait())
+            .await
+            .ok()
+            .and_then(Result::ok);
+    }
+
+    let outcome = if exit.is_some() {
+        StopOutcome::Graceful
+    } else {
+        signal_process_group(pid, Signal::SIGKILL);
+        match timeout(Duration::from_millis(timeouts.kill_timeout_ms), child.wait()).await {
+            Ok(Ok(status)) => {
+                exit = Some(status);
+                StopOutcome::Killed
+            }
+            _ => StopOutcome::Unresponsive,
+        }
+    };
+
+    // The leader is gone; sweep up anything it left behind in its group.
+    signal_process_group(pid, Signal::SIGKILL);
+
+    StopReport {
+        outcome,
+        acknowledged,
+        exit_code: exit.and_then(|status| status.code()),
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(threshold: usize) -> RestartPolicy {
+        RestartPolicy::new(RestartSettings {
+            crash_loop_threshold: threshold,
+            ..RestartSettings::default()
+        })
+    }
+
+    fn delay(decision: RestartDecision) -> Duration {
+        match decision {
+            RestartDecision::Restart { delay, .. } => delay,
+            other => panic!("expected a restart, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let mut policy = policy(100);
+        let start = Instant::now();
+        let delays: Vec<u64> = (0..7)
+            .map(|i| delay(policy.record_exit(start + Duration::from_millis(i))).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [1_000, 2_000, 4_000, 8_000, 16_000, 30_000, 30_000]);
+    }
+
+    #[test]
+    fn multiplier_below_one_keeps_the_initial_backoff() {
+        let mut policy = RestartPolicy::new(RestartSettings {
+            multiplier: 0.5,
+            ..RestartSettings::default()
+        });
+        let now = Instant::now();
+        policy.record_exit(now);
+        assert_eq!(delay(policy.record_exit(now)), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn crash_loop_after_threshold_exits_in_the_window() {
+        let mut policy = policy(3);
+        let start = Instant::now();
+        assert!(matches!(policy.record_exit(start), RestartDecision::Restart { attempt: 1, .. }));
+        assert!(matches!(
+            policy.record_exit(start + Duration::from_secs(1)),
+            RestartDecision::Restart { attempt: 2, .. }
+        ));
+        assert_eq!(
+            policy.record_exit(start + Duration::from_secs(2)),
+            RestartDecision::CrashLoop {
+                exits: 3,
+                window: Duration::from_secs(60)
+            }
+        );
+    }
+
+    #[test]
+    fn exits_outside_the_window_are_forgotten() {
+        let mut policy = policy(3);
+        let start = Instant::now();
+        policy.record_exit(start);
+        policy.record_exit(start + Duration::from_secs(1));
+        let later = start + Duration::from_secs(62);
+        assert_eq!(
+            policy.record_exit(later),
+            RestartDecision::Restart {
+                attempt: 1,
+                delay: Duration::from_secs(1)
+            }
+        );
+    }
+
+    #[test]
+    fn reset_and_disabled() {
+        let mut policy = policy(2);
+        let now = Instant::now();
+        policy.record_exit(now);
+        policy.reset();
+        assert!(matches!(policy.record_exit(now), RestartDecision::Restart { attempt: 1, .. }));
+
+        let mut disabled = RestartPolicy::new(RestartSettings {
+            enabled: false,
+            ..RestartSettings::default()
+        });
+        assert_eq!(disabled.record_exit(now), RestartDecision::Disabled);
+    }
+
+    /// `sh` leading its own process group with a background `sleep`, as the
+    /// daemon leads its workers. Returns the child and the sleep's pid.
+    #[cfg(not(windows))]
+    async fn group(script: &str) -> (Child, u32) {
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", &format!("{script} sleep 30 & echo $!; wait")])
+            .stdout(std::process::Stdio::piped())
+            .process_group(0)
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .await
+            .unwrap();
+        (child, line.trim().parse().unwrap())
+    }
+
+    /// Waits for `pid` to exit; a zombie nobody has reaped counts as gone.
+    #[cfg(not(windows))]
+    async fn gone(pid: u32) -> bool {
+        for _ in 0..50 {
+            let output = std::process::Command::new("ps")
+                .args(["-o", "stat=", "-p", &pid.to_string()])
+                .output()
+                .unwrap();
+            let stat = String::from_utf8_lossy(&output.stdout);
+            if stat.trim().is_empty() || stat.trim().starts_with('Z') {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        false
+    }
+
+    #[cfg(not(windows))]
+    fn timeouts() -> StopSettings {
+        StopSettings {
+            ack_timeout_ms: 300,
+            grace_timeout_ms: 300,
+            kill_timeout_ms: 2_000,
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn an_acknowledged_stop_is_graceful() {
+        let (mut child, sleeper) = group("").await;
+        let (phase, rx) = watch::channel(None);
+        phase.send_replace(Some("stopping".to_string()));
+
+        let report = stop_group(&mut child, &timeouts(), rx).await;
+        assert_eq!(report.outcome, StopOutcome::Graceful);
+        assert!(report.acknowledged);
+        assert!(report.elapsed_ms < 300, "{report:?}");
+        assert!(gone(sleeper).await, "SIGTERM reaches the whole group");
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn a_group_ignoring_sigterm_is_killed() {
+        let (mut child, sleeper) = group("trap '' TERM;").await;
+        let (_phase, rx) = watch::channel(None);
+
+        let report = stop_group(&mut child, &timeouts(), rx).await;
+        assert_eq!(report.outcome, StopOutcome::Killed);
+        assert!(!report.acknowledged);
+        assert_eq!(report.exit_code, None, "killed by a signal");
+        assert!(report.elapsed_ms >= 300, "{report:?}");
+        assert!(gone(sleeper).await, "SIGKILL reaches the whole group");
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn an_acknowledged_daemon_that_hangs_is_killed_after_the_grace() {
+        let (mut child, sleeper) = group("trap '' TERM;").await;
+        let (phase, rx) = watch::channel(None);
+        phase.send_replace(Some("stopping".to_string()));
+
+        let report = stop_group(&mut child, &timeouts(), rx).await;
+        assert_eq!(report.outcome, StopOutcome::Killed);
+        assert!(report.acknowledged);
+        assert!(report.elapsed_ms >= 300, "{report:?}");
+        assert!(gone(sleeper).await);
+    }
+}
@@ -0,0 +1,460 @@
+use tauri::{
+    menu::{CheckMenuItem, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
+    AppHandle, Wry,
+};
+
+use crate::snapshot::{AccountSnapshot, DaemonSnapshot};
+
+/// Menu ids of account entries are this prefix followed by the email.
+const ACCOUNT_PREFIX: &str = "account:";
+
+/// What the tray needs to know about the shell and the daemon.
+pub struct TrayInput<'a> {
+    pub running: bool,
+    /// Spawned but not ready yet, or waiting to be restarted.
+    pub starting: bool,
+    pub port: u16,
+    pub snapshot: Option<&'a DaemonSnapshot>,
+    pub last_error: Option<&'a str>,
+    /// `None` until the Claude settings have been checked.
+    pub config_healthy: Option<bool>,
+    pub now_ms: i64,
+}
+
+/// Everything the tray menu shows. Rebuilding a native menu while it is open
+/// can make it flicker or close, so `update_tray` only rebuilds when this
+/// changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuModel {
+    /// Non-clickable first line, e.g. `Running on :8080 · alice@…`.
+    pub header: String,
+    pub can_start: bool,
+    pub can_stop: bool,
+    /// Present while the proxy runs.
+    pub base_url: Option<String>,
+    pub show_repair: bool,
+    pub accounts: Vec<AccountEntry>,
+    /// Shown disabled in place of the account list when it is empty.
+    pub accounts_note: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountEntry {
+    pub email: String,
+    pub label: String,
+    pub current: bool,
+    pub enabled: bool,
+}
+
+impl MenuModel {
+    pub fn new(input: &TrayInput) -> Self {
+        let accounts: Vec<AccountEntry> = match input.snapshot {
+            Some(snapshot) if input.running => snapshot
+                .accounts
+                .iter()
+                .filter(|account| !account.email.is_empty())
+                .map(|account| AccountEntry {
+                    email: account.email.clone(),
+                    label: account_label(account, input.now_ms),
+                    current: snapshot.current_account.as_deref() == Some(account.email.as_str()),
+                    enabled: !account.is_invalid,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let accounts_note = if !input.running {
+            Some("Start the proxy to switch accounts".to_string())
+        } else if accounts.is_empty() {
+            Some("No accounts".to_string())
+        } else {
+            None
+        };
+        let port = input.snapshot.and_then(|snapshot| snapshot.port).unwrap_or(input.port);
+        let ready = input.running && !input.starting;
+        Self {
+            header: header(input, port),
+            can_start: !input.running && !input.starting,
+            can_stop: input.running || input.starting,
+            base_url: ready.then(|| format!("http://localhost:{port}")),
+            show_repair: input.config_healthy == Some(false),
+            accounts,
+            accounts_note,
+        }
+    }
+
+    pub fn build(&self, app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+        let menu = Menu::new(app)?;
+        menu.append(&MenuItem::with_id(app, "status-header", &self.header, false, None::<&str>)?)?;
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        menu.append(&MenuItem::with_id(app, "start-proxy", "Start Proxy", self.can_start, None::<&str>)?)?;
+        menu.append(&MenuItem::with_id(app, "stop-proxy", "Stop Proxy", self.can_stop, None::<&str>)?)?;
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        menu.append(&self.accounts_submenu(app)?)?;
+        if self.base_url.is_some() {
+            menu.append(&MenuItem::with_id(app, "copy-base-url", "Copy Base URL", true, None::<&str>)?)?;
+        }
+        menu.append(&MenuItem::with_id(
+            app,
+            "open-dashboard",
+            "Open Dashboard",
+            self.base_url.is_some(),
+            None::<&str>,
+        )?)?;
+        if self.show_repair {
+            menu.append(&MenuItem::with_id(app, "repair-config", "Repair Claude Config", true, None::<&str>)?)?;
+        }
+        menu.append(&MenuItem::with_id(app, "view-logs", "View Logs", true, None::<&str>)?)?;
+        menu.append(&PredefinedMenuItem::separator(app)?)?;
+        menu.append(&MenuItem::with_id(app, "quit-app", "Quit", true, None::<&str>)?)?;
+        Ok(menu)
+    }
+
+    fn accounts_submenu(&self, app: &AppHandle) -> tauri::Result<Submenu<Wry>> {
+        let submenu = Submenu::with_id(app, "accounts", "Accounts", true)?;
+        if let Some(note) = &self.accounts_note {
+            submenu.append(&MenuItem::with_id(app, "accounts-note", note, false, None::<&str>)?)?;
+            return Ok(submenu);
+        }
+        let items = self
+            .accounts
+            .iter()
+            .map(|entry| {
+                CheckMenuItem::with_id(
+                    app,
+                    format!("{ACCOUNT_PREFIX}{}", entry.email),
+                    &entry.label,
+                    entry.enabled,
+                    entry.current,
+                    None::<&str>,
+                )
+            })
+            .collect::<tauri::Result<Vec<_>>>()?;
+        let refs: Vec<&dyn IsMenuItem<Wry>> = items.iter().map(|item| item as &dyn IsMenuItem<Wry>).collect();
+        submenu.append_items(&refs)?;
+        Ok(submenu)
+    }
+}
+
+fn header(input: &TrayInput, port: u16) -> String {
+    if input.starting {
+        return if input.running {
+            format!("Starting on :{port}…")
+        } else {
+            "Restarting…".to_string()
+        };
+    }
+    if input.running {
+        return match input.snapshot.and_then(|snapshot| snapshot.current_account.as_deref()) {
+            Some(account) => format!("Running on :{port} · {}", short_account(account)),
+            None => format!("Running on :{port}"),
+        };
+    }
+    match input.last_error {
+        Some(error) => format!("Stopped · {}", truncate(error, 48)),
+        None => "Stopped".to_string(),
+    }
+}
+
+/// Accounts listed in the tooltip before the rest are summarised; Windows cuts
+/// tooltips off at 127 characters.
+const TOOLTIP_ACCOUNTS: usize = 2;
+
+/// The header line, plus a per-second countdown for each account that is
+/// cooling down. `update_tray` is re-run every second while any is.
+pub fn tooltip(input: &TrayInput) -> String {
+    let port = input.snapshot.and_then(|snapshot| snapshot.port).unwrap_or(input.port);
+    let mut lines = vec![if input.running || input.starting {
+        header(input, port)
+    } else {
+        match input.last_error {
+            Some(error) => truncate(error, 64),
+            None => "Proxy stopped".to_string(),
+        }
+    }];
+    let waits = input
+        .snapshot
+        .map(|snapshot| snapshot.cooling_down(input.now_ms))
+        .unwrap_or_default();
+    if !input.running {
+        if let Some((_, wait)) = waits.first() {
+            lines.push(format!("Next slot in {}", format_wait(*wait as u64)));
+        }
+        return lines.join("\n");
+    }
+    for (account, wait) in waits.iter().take(TOOLTIP_ACCOUNTS) {
+        lines.push(format!("⏳ {} · {}", short_account(&account.email), format_wait(*wait as u64)));
+    }
+    if waits.len() > TOOLTIP_ACCOUNTS {
+        lines.push(format!("+{} more cooling down", waits.len() - TOOLTIP_ACCOUNTS));
+    }
+    lines.join("\n")
+}
+
+/// `alice@example.com` -> `alice@…`.
+fn short_account(email: &str) -> String {
+    match email.split_once('@') {
+        Some((local, _)) => format!("{}@…", truncate(local, 16)),
+        None => truncate(email, 24),
+    }
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    let first_line = text.lines().next().unwrap_or_default();
+    if first_line.chars().count() <= max_chars {
+        first_line.to_string()
+    } else {
+        let kept: String = first_line.chars().take(max_chars.saturating_sub(1)).collect();
+        format!("{kept}…")
+    }
+}
+
+/// The email behind an account entry's menu id.
+pub fn account_email(id: &str) -> Option<&str> {
+    id.strip_prefix(ACCOUNT_PREFIX)
+}
+
+/// `alice@example.com · health 92 · ★`, with the rate-limit countdown or the
+/// invalid marker when they apply.
+fn account_label(account: &AccountSnapshot, now_ms: i64) -> String {
+    let mut parts = vec![
+        account.email.clone(),
+        format!("health {}", account.health_score.round() as i64),
+    ];
+    if account.is_invalid {
+        parts.push("✕ invalid".to_string());
+    } else if account.is_rate_limited {
+        let reset = account.next_available_at.or(account.rate_limit_reset_time);
+        match reset.map(|at| at - now_ms).filter(|wait| *wait > 0) {
+            Some(wait) => parts.push(format!("⏳ {}", format_countdown(wait as u64))),
+            None => parts.push("⏳ rate limited".to_string()),
+        }
+    } else if account.recommended {
+        parts.push("★".to_string());
+    }
+    parts.join(" · ")
+}
+
+/// To the second, for the tooltip.
+fn format_wait(ms: u64) -> String {
+    let secs = ms.div_ceil(1000);
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Coarse on purpose: the label only changes (and the menu only rebuilds)
+/// once a minute until the last minute.
+fn format_countdown(ms: u64) -> String {
+    let secs = ms.div_ceil(1000);
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m", secs.div_ceil(60))
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    fn account(email: &str) -> AccountSnapshot {
+        AccountSnapshot {
+            email: email.to_string(),
+            health_score: 91.6,
+            ..AccountSnapshot::default()
+        }
+    }
+
+    fn snapshot(accounts: Vec<AccountSnapshot>) -> DaemonSnapshot {
+        DaemonSnapshot {
+            port: Some(8080),
+            current_account: Some("alice@example.com".to_string()),
+            accounts,
+            ..DaemonSnapshot::default()
+        }
+    }
+
+    fn input(snapshot: Option<&DaemonSnapshot>) -> TrayInput<'_> {
+        TrayInput {
+            running: true,
+            starting: false,
+            port: 9000,
+            snapshot,
+            last_error: None,
+            config_healthy: Some(true),
+            now_ms: NOW,
+        }
+    }
+
+    #[test]
+    fn accounts_are_labelled_and_checked() {
+        let limited = AccountSnapshot {
+            is_rate_limited: true,
+            next_available_at: Some(NOW + 90_000),
+            ..account("bob@example.com")
+        };
+        let invalid = AccountSnapshot {
+            is_invalid: true,
+            ..account("carol@example.com")
+        };
+        let recommended = AccountSnapshot {
+            recommended: true,
+            ..account("alice@example.com")
+        };
+        let snapshot = snapshot(vec![recommended, limited, invalid, account("")]);
+        let model = MenuModel::new(&input(Some(&snapshot)));
+        assert_eq!(
+            model.accounts,
+            [
+                AccountEntry {
+                    email: "alice@example.com".to_string(),
+                    label: "alice@example.com · health 92 · ★".to_string(),
+                    current: true,
+                    enabled: true,
+                },
+                AccountEntry {
+                    email: "bob@example.com".to_string(),
+                    label: "bob@example.com · health 92 · ⏳ 2m".to_string(),
+                    current: false,
+                    enabled: true,
+                },
+                AccountEntry {
+                    email: "carol@example.com".to_string(),
+                    label: "carol@example.com · health 92 · ✕ invalid".to_string(),
+                    current: false,
+                    enabled: false,
+                },
+            ]
+        );
+        assert_eq!(model.accounts_note, None);
+    }
+
+    #[test]
+    fn account_list_notes() {
+        let empty = snapshot(vec![]);
+        let model = MenuModel::new(&input(Some(&empty)));
+        assert_eq!(model.accounts_note.as_deref(), Some("No accounts"));
+
+        let stopped = TrayInput {
+            running: false,
+            ..input(Some(&empty))
+        };
+        let model = MenuModel::new(&stopped);
+        assert!(model.accounts.is_empty());
+        assert_eq!(model.accounts_note.as_deref(), Some("Start the proxy to switch accounts"));
+    }
+
+    #[test]
+    fn account_ids_round_trip() {
+        assert_eq!(account_email("account:alice@example.com"), Some("alice@example.com"));
+        assert_eq!(account_email("quit-app"), None);
+    }
+
+    #[test]
+    fn header_and_entries_follow_the_proxy_state() {
+        let snapshot = snapshot(vec![account("alice@example.com")]);
+        let running = MenuModel::new(&input(Some(&snapshot)));
+        assert_eq!(running.header, "Running on :8080 · alice@…");
+        assert!(!running.can_start && running.can_stop);
+        assert_eq!(running.base_url.as_deref(), Some("http://localhost:8080"));
+        assert!(!running.show_repair);
+
+        let starting = MenuModel::new(&TrayInput {
+            starting: true,
+            ..input(None)
+        });
+        assert_eq!(starting.header, "Starting on :9000…");
+        assert!(!starting.can_start && starting.can_stop);
+        assert_eq!(starting.base_url, None);
+
+        let restarting = MenuModel::new(&TrayInput {
+            running: false,
+            starting: true,
+            ..input(None)
+        });
+        assert_eq!(restarting.header, "Restarting…");
+
+        let error = "listen EADDRINUSE: address already in use 127.0.0.1:9000\n    at stack";
+        let stopped = MenuModel::new(&TrayInput {
+            running: false,
+            last_error: Some(error),
+            config_healthy: Some(false),
+            ..input(None)
+        });
+        assert_eq!(stopped.header, "Stopped · listen EADDRINUSE: address already in use 127.0…");
+        assert!(stopped.can_start && !stopped.can_stop);
+        assert!(stopped.show_repair);
+    }
+
+    #[test]
+    fn repair_shows_only_after_a_failed_check() {
+        let unchecked = MenuModel::new(&TrayInput {
+            config_healthy: None,
+            ..input(None)
+        });
+        assert!(!unchecked.show_repair);
+    }
+
+    fn cooling(email: &str, wait_ms: i64) -> AccountSnapshot {
+        AccountSnapshot {
+            is_rate_limited: true,
+            next_available_at: Some(NOW + wait_ms),
+            ..account(email)
+        }
+    }
+
+    #[test]
+    fn tooltip_counts_down_per_account() {
+        let snapshot = snapshot(vec![
+            account("alice@example.com"),
+            cooling("dave@example.com", 3_725_000),
+            cooling("bob@example.com", 61_500),
+            cooling("carol@example.com", 4_200),
+            cooling("erin@example.com", -1_000),
+        ]);
+        assert_eq!(
+            tooltip(&input(Some(&snapshot))),
+            "Running on :8080 · alice@…\n⏳ carol@… · 5s\n⏳ bob@… · 1m 02s\n+1 more cooling down"
+        );
+    }
+
+    #[test]
+    fn stopped_tooltip_shows_the_error_and_next_slot() {
+        let snapshot = snapshot(vec![cooling("bob@example.com", 90_000)]);
+        let stopped = TrayInput {
+            running: false,
+            ..input(Some(&snapshot))
+        };
+        assert_eq!(tooltip(&stopped), "Proxy stopped\nNext slot in 1m 30s");
+        let failed = TrayInput {
+            running: false,
+            last_error: Some("boom\nstack"),
+            ..input(None)
+        };
+        assert_eq!(tooltip(&failed), "boom");
+    }
+
+    #[test]
+    fn waits_are_exact_to_the_second() {
+        assert_eq!(format_wait(1), "1s");
+        assert_eq!(format_wait(59_000), "59s");
+        assert_eq!(format_wait(60_000), "1m 00s");
+        assert_eq!(format_wait(3_600_000), "1h 00m");
+        assert_eq!(format_wait(3_725_000), "1h 02m");
+    }
+
+    #[test]
+    fn countdowns_are_coarse_in_the_menu() {
+        assert_eq!(format_countdown(59_000), "59s");
+        assert_eq!(format_countdown(61_000), "2m");
+        assert_eq!(format_countdown(3_725_000), "1h 2m");
+    }
+}
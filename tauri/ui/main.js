@@ -50,13 +50,27 @@ const accountEl = $('account-value');
 const updatedEl = $('updated-value');
 const errorEl = $('error-box');
 const errorIndicatorEl = $('error-indicator');
+const warningListEl = $('warning-list');
 const configWarningEl = $('config-warning');
 const configPathEl = $('config-path');
+const configDiffEl = $('config-diff');
+const configDiffListEl = $('config-diff-list');
+const configApplyBtn = $('config-apply');
+const configCancelBtn = $('config-cancel');
 const startBtn = $('start-btn');
 const stopBtn = $('stop-btn');
 const dashboardBtn = $('dashboard-btn');
 const logsBtn = $('logs-btn');
+const logViewerEl = $('log-viewer');
+const logFileEl = $('log-file');
+const logSearchEl = $('log-search');
+const logLevelsEl = $('log-levels');
+const logLinesEl = $('log-lines');
+const logOlderBtn = $('log-older');
+const logExternalBtn = $('log-external');
 const repairBtn = $('repair-btn');
+const detachBtn = $('detach-btn');
+const diagnosticsBtn = $('diagnostics-btn');
 
 function setIndicator(state) {
   if (!indicatorEl) return;
@@ -94,6 +108,7 @@ function setBusy(isBusy) {
   if (startBtn) startBtn.disabled = isBusy;
   if (stopBtn) stopBtn.disabled = isBusy;
   if (repairBtn) repairBtn.disabled = isBusy;
+  if (detachBtn) detachBtn.disabled = isBusy;
   if (dashboardBtn) dashboardBtn.disabled = isBusy;
 }
 
@@ -108,6 +123,30 @@ function setError(message) {
   }
 }
 
+// Non-fatal daemon stderr, newest first. Shown below the status log so a
+// deprecation notice doesn't read like an outage. Failures writing
+// desktop.log go first, since the log can't record them.
+function renderWarnings(warnings, logFailures) {
+  if (!warningListEl) return;
+  const shown = (warnings || []).slice(0, 5);
+  if (logFailures?.count) {
+    shown.unshift({
+      message: logFailures.last_error,
+      count: logFailures.count,
+      last_seen: logFailures.last_seen,
+    });
+  }
+  warningListEl.replaceChildren(
+    ...shown.map((warning) => {
+      const item = document.createElement('li');
+      item.textContent = warning.count > 1 ? `${warning.message} (×${warning.count})` : warning.message;
+      item.title = `Last seen ${formatDate(warning.last_seen)}`;
+      return item;
+    })
+  );
+  warningListEl.classList.toggle('hidden', shown.length === 0);
+}
+
 function updateUI(status) {
   // Handle null/undefined status
   if (!status) {
@@ -119,21 +158,35 @@ function updateUI(status) {
 
   const running = !!status.running;
   const hasError = Boolean(status.last_error);
-  setIndicator(running ? 'running' : hasError ? 'warning' : 'idle');
+  const phase = status.readiness?.phase;
+  const degraded = phase === 'degraded';
+  setIndicator(running && !degraded ? 'running' : hasError || degraded ? 'warning' : 'idle');
 
   if (statusTextEl) {
-    statusTextEl.textContent = running ? 'Proxy Running' : 'Proxy Stopped';
+    statusTextEl.textContent = {
+      starting: 'Proxy Starting…',
+      ready: 'Proxy Running',
+      degraded: 'Proxy Degraded',
+    }[phase] || (running ? 'Proxy Running' : 'Proxy Stopped');
   }
 
   if (statusMetaEl) {
-    const port = status.snapshot?.port ?? '—';
+    const port = status.port ?? '—';
     const account = status.snapshot?.currentAccount || 'No Account';
+    const health = status.health;
+    const accounts = health ? ` · ${health.available}/${health.total} available` : '';
     statusMetaEl.textContent = status.snapshot
-      ? `Port ${port} · ${account}`
+      ? `Port ${port} · ${account}${accounts}`
       : 'Not started';
+    if (status.adopted_pid) {
+      statusMetaEl.textContent = `Port ${port} · re-attached to pid ${status.adopted_pid}${accounts}`;
+    }
+    if (degraded && status.readiness.reason) {
+      statusMetaEl.textContent += ` · ${status.readiness.reason}`;
+    }
   }
 
-  if (portEl) portEl.textContent = status.snapshot?.port ?? '8080';
+  if (portEl) portEl.textContent = status.port ?? '—';
   if (lanEl) {
     const lanEnabled = status.snapshot?.lanEnabled;
     lanEl.textContent = lanEnabled ? 'Enabled' : 'Disabled';
@@ -144,6 +197,7 @@ function updateUI(status) {
 
   // Update error display
   setError(status.last_error || (running ? 'Proxy running normally' : 'Ready'));
+  renderWarnings(status.warnings, status.log_failures);
 
   // Update button states
   if (startBtn) startBtn.disabled = running;
@@ -154,7 +208,7 @@ function updateUI(status) {
   if (configWarningEl) {
     if (configStatus && !configStatus.healthy) {
       configWarningEl.classList.remove('hidden');
-      if (configPathEl) configPathEl.textContent = configStatus.settings_path || '~/.claude/settings.json';
+      if (configPathEl) configPathEl.textContent = configStatus.settingsPath || '~/.claude/settings.json';
     } else {
       configWarningEl.classList.add('hidden');
     }
@@ -172,6 +226,26 @@ async function refreshStatus() {
   }
 }
 
+// Starts the proxy; if the port is taken and the shell found a free one,
+// offer to start there instead.
+async function startProxy() {
+  try {
+    await invoke('start_proxy');
+  } catch (error) {
+    const status = await invoke('fetch_status').catch(() => null);
+    const conflict = status?.port_conflict;
+    if (!conflict?.suggested) throw error;
+    const holder = conflict.owner
+      ? `pid ${conflict.owner.pid}${conflict.owner.command ? ` (${conflict.owner.command})` : ''}`
+      : 'another process';
+    const retry = window.confirm(
+      `Port ${conflict.port} is in use by ${holder}.\n\nStart on port ${conflict.suggested} instead?`
+    );
+    if (!retry) throw error;
+    await invoke('start_proxy', { port: conflict.suggested });
+  }
+}
+
 async function guarded(action, successMessage) {
   setBusy(true);
   try {
@@ -188,10 +262,24 @@ async function guarded(action, successMessage) {
   }
 }
 
+// The shell pushes status, log lines and errors as they happen.
+async function subscribeToEvents() {
+  const tauri = await waitForTauri().catch(() => null);
+  const listen = tauri?.event?.listen;
+  if (!listen) return false;
+
+  await listen('proxy://status', (event) => updateUI(event.payload));
+  await listen('proxy://error', (event) => setError(event.payload?.message));
+  await listen('proxy://log', () => tailLog());
+  // A second launch brought this window forward; make sure it is current.
+  await listen('app://second-instance', () => refreshStatus());
+  return true;
+}
+
 // Event listeners with null checks
 if (startBtn) {
   startBtn.addEventListener('click', () =>
-    guarded(() => invoke('start_proxy'), 'Proxy started successfully')
+    guarded(startProxy, 'Proxy started successfully')
   );
 }
 
@@ -211,20 +299,253 @@ if (dashboardBtn) {
   });
 }
 
+function formatSize(bytes) {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
+// In-app log viewer. Pages backwards through the selected file; while the
+// live log is selected, new lines are tailed as `proxy://log` events arrive.
+const MAX_LOG_LINES = 2000;
+const logView = { path: null, start: 0, end: 0, tailing: false, pending: false, dirty: false };
+
+function logFilter() {
+  const boxes = [...(logLevelsEl?.querySelectorAll('input') || [])];
+  const checked = boxes.filter((box) => box.checked).map((box) => box.value);
+  const pattern = logSearchEl?.value.trim() || null;
+  // With every level ticked, also keep lines that carry no level label.
+  return {
+    levels: checked.length === boxes.length ? [] : checked,
+    pattern,
+    ignoreCase: pattern === pattern?.toLowerCase(),
+  };
+}
+
+function renderEntry(entry) {
+  const row = document.createElement('div');
+  row.className = `log-line log-${(entry.level || 'raw').toLowerCase()}`;
+  row.title = entry.timestamp;
+  row.textContent = entry.level ? `[${entry.level}] ${entry.line}` : entry.line;
+  return row;
+}
+
+function trimLogLines(fromTop) {
+  const rows = logLinesEl.querySelectorAll('.log-line');
+  const excess = rows.length - MAX_LOG_LINES;
+  if (excess <= 0) return;
+  const doomed = fromTop ? [...rows].slice(0, excess) : [...rows].slice(-excess);
+  doomed.forEach((row) => row.remove());
+}
+
+async function loadLogPage(reset) {
+  if (!logLinesEl) return;
+  const before = reset ? null : logView.start;
+  try {
+    const page = await invoke('read_logs', { path: logView.path, before, filter: logFilter() });
+    const atBottom = logLinesEl.scrollTop + logLinesEl.clientHeight >= logLinesEl.scrollHeight - 8;
+    if (reset) {
+      logLinesEl.querySelectorAll('.log-line').forEach((row) => row.remove());
+      logView.end = page.end;
+    }
+    logView.start = page.start;
+    const previousHeight = logLinesEl.scrollHeight;
+    logOlderBtn.after(...page.entries.map(renderEntry));
+    logOlderBtn.classList.toggle('hidden', page.start === 0);
+    if (reset || atBottom) {
+      logLinesEl.scrollTop = logLinesEl.scrollHeight;
+    } else {
+      logLinesEl.scrollTop += logLinesEl.scrollHeight - previousHeight;
+    }
+    trimLogLines(false);
+  } catch (error) {
+    setError(error?.message || String(error));
+  }
+}
+
+async function tailLog() {
+  if (!logView.tailing || logView.path) return;
+  // Lines that arrive mid-call are picked up by one more pass afterwards.
+  if (logView.pending) {
+    logView.dirty = true;
+    return;
+  }
+  logView.pending = true;
+  logView.dirty = false;
+  try {
+    const page = await invoke('tail_logs', { from: logView.end, filter: logFilter() });
+    if (page.rotated) {
+      await loadLogPage(true);
+      return;
+    }
+    logView.end = page.end;
+    if (page.entries.length === 0) return;
+    const atBottom = logLinesEl.scrollTop + logLinesEl.clientHeight >= logLinesEl.scrollHeight - 8;
+    logLinesEl.append(...page.entries.map(renderEntry));
+    trimLogLines(true);
+    if (atBottom) logLinesEl.scrollTop = logLinesEl.scrollHeight;
+  } catch (error) {
+    console.error('Failed to tail log:', error);
+  } finally {
+    logView.pending = false;
+    if (logView.dirty) tailLog();
+  }
+}
+
+async function refreshLogFiles() {
+  if (!logFileEl) return;
+  const logs = await invoke('list_logs').catch(() => []);
+  logFileEl.replaceChildren(
+    ...logs.map((log) => {
+      const option = document.createElement('option');
+      option.value = log.current ? '' : log.path;
+      const label = log.current
+        ? 'Current log'
+        : log.modified ? new Date(log.modified).toLocaleString() : log.name;
+      option.textContent = `${label} · ${formatSize(log.size)}${log.compressed ? ' · gz' : ''}`;
+      option.title = log.name;
+      return option;
+    })
+  );
+  logFileEl.value = logView.path || '';
+}
+
+async function toggleLogViewer() {
+  if (!logViewerEl) return;
+  const opening = logViewerEl.classList.contains('hidden');
+  logViewerEl.classList.toggle('hidden', !opening);
+  logView.tailing = opening;
+  if (opening) {
+    await refreshLogFiles();
+    await loadLogPage(true);
+  }
+}
+
 if (logsBtn) {
-  logsBtn.addEventListener('click', async () => {
+  logsBtn.addEventListener('click', toggleLogViewer);
+}
+
+if (logFileEl) {
+  logFileEl.addEventListener('change', () => {
+    logView.path = logFileEl.value || null;
+    loadLogPage(true);
+  });
+}
+
+if (logSearchEl) {
+  let searchTimer = null;
+  logSearchEl.addEventListener('input', () => {
+    clearTimeout(searchTimer);
+    searchTimer = setTimeout(() => loadLogPage(true), 300);
+  });
+}
+
+if (logLevelsEl) {
+  logLevelsEl.addEventListener('change', () => loadLogPage(true));
+}
+
+if (logOlderBtn) {
+  logOlderBtn.addEventListener('click', () => loadLogPage(false));
+}
+
+if (logExternalBtn) {
+  logExternalBtn.addEventListener('click', async () => {
     try {
-      await invoke('view_logs');
+      await invoke('view_logs', logView.path ? { path: logView.path } : {});
     } catch (error) {
       setError(error?.message || String(error));
     }
   });
 }
 
-if (repairBtn) {
-  repairBtn.addEventListener('click', () =>
-    guarded(() => invoke('repair_claude_config'), 'Claude CLI configured successfully')
+function describeChange(entry) {
+  if (entry.kind === 'added') return `add ${entry.expected}`;
+  if (entry.kind === 'changed') return `${entry.current} → ${entry.expected}`;
+  return entry.expected;
+}
+
+// One row per managed key; keys that would change start checked.
+function renderConfigDiff(config) {
+  if (!configDiffEl || !configDiffListEl) return;
+  configDiffListEl.replaceChildren(
+    ...(config.diff || []).map((entry) => {
+      const item = document.createElement('li');
+      item.className = `config-diff-${entry.kind}`;
+      const label = document.createElement('label');
+      const box = document.createElement('input');
+      box.type = 'checkbox';
+      box.value = entry.key;
+      box.checked = entry.kind !== 'unchanged';
+      box.disabled = entry.kind === 'unchanged';
+      const key = document.createElement('code');
+      key.textContent = entry.key;
+      const change = document.createElement('span');
+      change.textContent = describeChange(entry);
+      change.title = change.textContent;
+      label.append(box, key, change);
+      item.append(label);
+      return item;
+    })
   );
+  configDiffEl.classList.remove('hidden');
+}
+
+function hideConfigDiff() {
+  if (configDiffEl) configDiffEl.classList.add('hidden');
+}
+
+if (repairBtn) {
+  repairBtn.addEventListener('click', async () => {
+    try {
+      const config = await invoke('check_claude_config');
+      if (config.healthy) {
+        hideConfigDiff();
+        setError('Claude CLI is already configured');
+        return;
+      }
+      configWarningEl?.classList.remove('hidden');
+      renderConfigDiff(config);
+    } catch (error) {
+      setError(error?.message || String(error));
+    }
+  });
+}
+
+if (detachBtn) {
+  detachBtn.addEventListener('click', () => {
+    hideConfigDiff();
+    guarded(() => invoke('detach_claude_config'), 'Proxy settings removed from Claude CLI');
+  });
+}
+
+if (configApplyBtn) {
+  configApplyBtn.addEventListener('click', () => {
+    const keys = [...configDiffListEl.querySelectorAll('input:checked:not(:disabled)')].map((box) => box.value);
+    if (keys.length === 0) {
+      setError('Select at least one setting to change');
+      return;
+    }
+    hideConfigDiff();
+    guarded(() => invoke('repair_claude_config', { keys }), 'Claude CLI configured successfully');
+  });
+}
+
+if (configCancelBtn) {
+  configCancelBtn.addEventListener('click', hideConfigDiff);
+}
+
+if (diagnosticsBtn) {
+  diagnosticsBtn.addEventListener('click', async () => {
+    diagnosticsBtn.disabled = true;
+    try {
+      const written = await invoke('export_diagnostics');
+      if (written) setError(`Diagnostics saved to ${written}`);
+    } catch (error) {
+      setError(error?.message || String(error));
+    } finally {
+      diagnosticsBtn.disabled = false;
+    }
+  });
 }
 
 // Initial load with retry
@@ -235,9 +556,14 @@ function initApp() {
 
   // Wait a bit for Tauri to be ready
   waitForTauri()
-    .then(() => {
+    .then(async () => {
       refreshStatus();
-      setInterval(refreshStatus, 5000);
+      const subscribed = await subscribeToEvents().catch(() => false);
+      if (!subscribed) {
+        setInterval(refreshStatus, 5000);
+      }
+      // Claude settings can change behind our back; re-check when the window regains focus.
+      window.addEventListener('focus', refreshStatus);
     })
     .catch((error) => {
       setError(error?.message || String(error));